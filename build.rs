@@ -0,0 +1,8 @@
+fn main() {
+    // The default host for evaluating the platforms of build dependencies.
+    println!(
+        "cargo:rustc-env=CRATE_DEPS_HOST={}",
+        std::env::var("TARGET").unwrap()
+    );
+    println!("cargo:rerun-if-changed=build.rs");
+}
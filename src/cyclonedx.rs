@@ -0,0 +1,316 @@
+//! CycloneDX 1.5 software bills of materials.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::purl::purl;
+use crate::{DependencyGraph, Package, ResolvedPackage};
+
+/// A CycloneDX 1.5 bill of materials for a dependency graph.
+///
+/// Every package in the graph becomes a `library` component identified by
+/// its purl, with the SHA-256 checksum from the registry index as its hash.
+/// If the graph has a single root, it's described as the BOM's subject in
+/// `metadata.component` instead.
+///
+/// Licenses are only included where they're provided with
+/// [`license`](Self::license), e.g. from
+/// [`Resolver::licenses`](crate::Resolver::licenses).
+#[derive(Clone, Debug)]
+pub struct CycloneDx<'a> {
+    graph: &'a DependencyGraph,
+    licenses: BTreeMap<Package, String>,
+}
+
+impl<'a> CycloneDx<'a> {
+    pub fn new(graph: &'a DependencyGraph) -> Self {
+        Self {
+            graph,
+            licenses: BTreeMap::new(),
+        }
+    }
+
+    /// Set the license of a package, as an SPDX license expression such as
+    /// `MIT OR Apache-2.0`.
+    pub fn license(mut self, package: &Package, expression: &str) -> Self {
+        self.licenses
+            .insert(package.clone(), expression.to_string());
+        self
+    }
+
+    /// Write the BOM in the JSON format.
+    #[cfg(feature = "serde")]
+    pub fn write_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        use serde_json::{json, Value};
+
+        let component = |package: &ResolvedPackage| {
+            let mut component = json!({
+                "type": "library",
+                "bom-ref": purl(package),
+                "name": package.package.name,
+                "version": package.package.version,
+                "purl": purl(package),
+            });
+            if let Some(checksum) = &package.checksum {
+                component["hashes"] = json!([{ "alg": "SHA-256", "content": checksum }]);
+            }
+            if let Some(license) = self.licenses.get(&package.package) {
+                component["licenses"] = json!([{ "expression": license }]);
+            }
+            component
+        };
+
+        let (subject, components) = self.components();
+        let mut metadata = json!({
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+            },
+        });
+        if let Some(subject) = subject {
+            metadata["component"] = component(subject);
+        }
+        let bom = json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": metadata,
+            "components": components.into_iter().map(component).collect::<Vec<_>>(),
+            "dependencies": self
+                .dependencies()
+                .into_iter()
+                .map(|(package, deps)| json!({ "ref": package, "dependsOn": deps }))
+                .collect::<Vec<Value>>(),
+        });
+        serde_json::to_writer_pretty(&mut *w, &bom)?;
+        writeln!(w)
+    }
+
+    /// Write the BOM in the XML format.
+    pub fn write_xml<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let (subject, components) = self.components();
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            w,
+            r#"<bom xmlns="http://cyclonedx.org/schema/bom/1.5" version="1">"#
+        )?;
+        writeln!(w, "  <metadata>")?;
+        writeln!(w, "    <tools>")?;
+        writeln!(w, "      <components>")?;
+        writeln!(w, r#"        <component type="application">"#)?;
+        writeln!(w, "          <name>{}</name>", env!("CARGO_PKG_NAME"))?;
+        writeln!(
+            w,
+            "          <version>{}</version>",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        writeln!(w, "        </component>")?;
+        writeln!(w, "      </components>")?;
+        writeln!(w, "    </tools>")?;
+        if let Some(subject) = subject {
+            self.write_xml_component(w, subject, "    ")?;
+        }
+        writeln!(w, "  </metadata>")?;
+        writeln!(w, "  <components>")?;
+        for package in components {
+            self.write_xml_component(w, package, "    ")?;
+        }
+        writeln!(w, "  </components>")?;
+        writeln!(w, "  <dependencies>")?;
+        for (package, deps) in self.dependencies() {
+            if deps.is_empty() {
+                writeln!(w, r#"    <dependency ref="{}"/>"#, escape(&package))?;
+                continue;
+            }
+            writeln!(w, r#"    <dependency ref="{}">"#, escape(&package))?;
+            for dep in deps {
+                writeln!(w, r#"      <dependency ref="{}"/>"#, escape(&dep))?;
+            }
+            writeln!(w, "    </dependency>")?;
+        }
+        writeln!(w, "  </dependencies>")?;
+        writeln!(w, "</bom>")
+    }
+
+    fn write_xml_component<W: Write>(
+        &self,
+        w: &mut W,
+        package: &ResolvedPackage,
+        indent: &str,
+    ) -> io::Result<()> {
+        let purl = escape(&purl(package));
+        writeln!(w, r#"{indent}<component type="library" bom-ref="{purl}">"#)?;
+        writeln!(
+            w,
+            "{indent}  <name>{}</name>",
+            escape(&package.package.name)
+        )?;
+        writeln!(
+            w,
+            "{indent}  <version>{}</version>",
+            escape(&package.package.version)
+        )?;
+        if let Some(checksum) = &package.checksum {
+            writeln!(w, "{indent}  <hashes>")?;
+            writeln!(
+                w,
+                r#"{indent}    <hash alg="SHA-256">{}</hash>"#,
+                escape(checksum)
+            )?;
+            writeln!(w, "{indent}  </hashes>")?;
+        }
+        if let Some(license) = self.licenses.get(&package.package) {
+            writeln!(w, "{indent}  <licenses>")?;
+            writeln!(
+                w,
+                "{indent}    <expression>{}</expression>",
+                escape(license)
+            )?;
+            writeln!(w, "{indent}  </licenses>")?;
+        }
+        writeln!(w, "{indent}  <purl>{purl}</purl>")?;
+        writeln!(w, "{indent}</component>")
+    }
+
+    /// Split the packages into the BOM's subject, if there's a single root,
+    /// and its other components.
+    fn components(&self) -> (Option<&'a ResolvedPackage>, Vec<&'a ResolvedPackage>) {
+        let mut roots = self.graph.roots();
+        let subject = match (roots.next(), roots.next()) {
+            (Some(root), None) => self.graph.package(root),
+            _ => None,
+        };
+        let components = self
+            .graph
+            .resolved_packages()
+            .filter(|p| Some(&p.package) != subject.map(|s| &s.package))
+            .collect();
+        (subject, components)
+    }
+
+    /// Get the purls of the direct dependencies of every package.
+    fn dependencies(&self) -> Vec<(String, BTreeSet<String>)> {
+        self.graph
+            .resolved_packages()
+            .map(|package| {
+                let deps = self
+                    .graph
+                    .dependencies(&package.package)
+                    .filter_map(|edge| self.graph.package(&edge.to))
+                    .map(purl)
+                    .collect();
+                (purl(package), deps)
+            })
+            .collect()
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{edge, package, resolved};
+    use crate::{DepKind, CRATES_IO_SOURCES};
+
+    fn graph() -> (DependencyGraph, ResolvedPackage) {
+        let root = resolved(&package("root", "1.0.0"), CRATES_IO_SOURCES[0], &[]);
+        let a = resolved(&package("a", "1.0.0+build.1"), CRATES_IO_SOURCES[1], &[]);
+        let b = resolved(
+            &package("b", "0.1.0"),
+            "sparse+https://example.com/index/",
+            &[],
+        );
+        let mut graph = DependencyGraph::new();
+        graph.add_root(root.clone());
+        graph.add_package(a.clone());
+        graph.add_package(b.clone());
+        graph.add_edge(edge(&root.package, &a.package, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root.package, &b.package, DepKind::Normal, &[]));
+        graph.add_edge(edge(&a.package, &b.package, DepKind::Normal, &[]));
+        (graph, root)
+    }
+
+    #[test]
+    fn xml() {
+        let (graph, root) = graph();
+        let mut xml = Vec::new();
+        CycloneDx::new(&graph)
+            .license(&root.package, "MIT <or> Apache-2.0")
+            .write_xml(&mut xml)
+            .unwrap();
+        let xml = String::from_utf8(xml).unwrap();
+
+        let metadata = &xml[xml.find("<metadata>").unwrap()..xml.find("</metadata>").unwrap()];
+        assert!(metadata.contains(r#"<component type="library" bom-ref="pkg:cargo/root@1.0.0">"#));
+        assert!(metadata.contains("<expression>MIT &lt;or&gt; Apache-2.0</expression>"));
+        let components = &xml[xml
+            .find("<components>\n    <component type=\"library\"")
+            .unwrap()..];
+        assert!(!components.contains("bom-ref=\"pkg:cargo/root@1.0.0\">"));
+        assert!(xml.contains(&format!(r#"<hash alg="SHA-256">{:0>64}</hash>"#, "a")));
+        assert!(xml.contains(concat!(
+            "    <dependency ref=\"pkg:cargo/a@1.0.0%2Bbuild.1\">\n",
+            "      <dependency ref=\"pkg:cargo/b@0.1.0?repository_url=https://example.com/index/\"/>\n",
+            "    </dependency>\n",
+        )));
+        assert!(xml.contains(
+            "\n    <dependency ref=\"pkg:cargo/b@0.1.0?repository_url=https://example.com/index/\"/>\n"
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        let (graph, root) = graph();
+        let mut json = Vec::new();
+        CycloneDx::new(&graph)
+            .license(&root.package, "MIT")
+            .write_json(&mut json)
+            .unwrap();
+        let json = serde_json::from_slice::<serde_json::Value>(&json).unwrap();
+
+        assert_eq!(json["bomFormat"], "CycloneDX");
+        assert_eq!(json["specVersion"], "1.5");
+        assert_eq!(
+            json["metadata"]["component"],
+            serde_json::json!({
+                "type": "library",
+                "bom-ref": "pkg:cargo/root@1.0.0",
+                "name": "root",
+                "version": "1.0.0",
+                "purl": "pkg:cargo/root@1.0.0",
+                "hashes": [{ "alg": "SHA-256", "content": format!("{:0>64}", "root") }],
+                "licenses": [{ "expression": "MIT" }],
+            })
+        );
+        assert_eq!(
+            json["components"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|c| c["name"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(
+            json["dependencies"][2],
+            serde_json::json!({
+                "ref": "pkg:cargo/root@1.0.0",
+                "dependsOn": [
+                    "pkg:cargo/a@1.0.0%2Bbuild.1",
+                    "pkg:cargo/b@0.1.0?repository_url=https://example.com/index/",
+                ],
+            })
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Graphviz DOT and Mermaid diagrams of dependency graphs.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::{DepKind, DependencyGraph, Package};
+
+/// A diagram of a dependency graph, rendered as Graphviz DOT or as a Mermaid
+/// flowchart.
+#[derive(Clone, Debug)]
+pub struct Diagram<'a> {
+    graph: &'a DependencyGraph,
+    color_kinds: bool,
+    cluster_versions: bool,
+    edge_features: bool,
+}
+
+/// A node in the diagram.
+struct Node<'a> {
+    id: String,
+    package: &'a Package,
+    kind: DepKind,
+}
+
+impl<'a> Diagram<'a> {
+    pub fn new(graph: &'a DependencyGraph) -> Self {
+        Self {
+            graph,
+            color_kinds: false,
+            cluster_versions: false,
+            edge_features: false,
+        }
+    }
+
+    /// Color packages that are only reached through build dependencies or
+    /// dev-dependencies (see [`DependencyGraph::kinds`]).
+    pub fn color_kinds(mut self) -> Self {
+        self.color_kinds = true;
+        self
+    }
+
+    /// Group the versions of a crate that appears more than once into a
+    /// cluster.
+    pub fn cluster_versions(mut self) -> Self {
+        self.cluster_versions = true;
+        self
+    }
+
+    /// Label edges to optional dependencies with the features that activate
+    /// them.
+    pub fn edge_features(mut self) -> Self {
+        self.edge_features = true;
+        self
+    }
+
+    /// Write the diagram in the Graphviz DOT language.
+    pub fn write_dot<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let nodes = self.nodes();
+        writeln!(w, "digraph dependencies {{")?;
+        writeln!(w, "    node [shape=box];")?;
+        for node in nodes.values() {
+            write!(
+                w,
+                "    {} [label=\"{} v{}\"",
+                node.id, node.package.name, node.package.version
+            )?;
+            if let Some(color) = self.color(node.kind) {
+                write!(w, ", style=filled, fillcolor=\"{color}\"")?;
+            }
+            writeln!(w, "];")?;
+        }
+        for (i, (name, versions)) in self.clusters(&nodes).into_iter().enumerate() {
+            writeln!(w, "    subgraph cluster_{i} {{")?;
+            writeln!(w, "        label=\"{name}\";")?;
+            for node in versions {
+                writeln!(w, "        {};", node.id)?;
+            }
+            writeln!(w, "    }}")?;
+        }
+        for ((from, to), features) in self.edges() {
+            write!(w, "    {} -> {}", nodes[from].id, nodes[to].id)?;
+            if self.edge_features && !features.is_empty() {
+                write!(w, " [label=\"{}\"]", join(&features))?;
+            }
+            writeln!(w, ";")?;
+        }
+        writeln!(w, "}}")
+    }
+
+    /// Write the diagram as a Mermaid flowchart.
+    pub fn write_mermaid<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let nodes = self.nodes();
+        let clusters = self.clusters(&nodes);
+        let clustered = clusters
+            .values()
+            .flatten()
+            .map(|node| node.package)
+            .collect::<BTreeSet<_>>();
+
+        writeln!(w, "flowchart TD")?;
+        for node in nodes.values() {
+            if !clustered.contains(node.package) {
+                writeln!(w, "    {}", mermaid_node(node))?;
+            }
+        }
+        for (i, (name, versions)) in clusters.iter().enumerate() {
+            writeln!(w, "    subgraph cluster_{i} [\"{name}\"]")?;
+            for node in versions {
+                writeln!(w, "        {}", mermaid_node(node))?;
+            }
+            writeln!(w, "    end")?;
+        }
+        for ((from, to), features) in self.edges() {
+            let (from, to) = (&nodes[from].id, &nodes[to].id);
+            if self.edge_features && !features.is_empty() {
+                writeln!(w, "    {from} -->|\"{}\"| {to}", join(&features))?;
+            } else {
+                writeln!(w, "    {from} --> {to}")?;
+            }
+        }
+        if self.color_kinds {
+            for (kind, class) in [(DepKind::Build, "build"), (DepKind::Development, "dev")] {
+                let ids = nodes
+                    .values()
+                    .filter(|node| node.kind == kind)
+                    .map(|node| node.id.as_str())
+                    .collect::<Vec<_>>();
+                if !ids.is_empty() {
+                    let color = self.color(kind).unwrap();
+                    writeln!(w, "    classDef {class} fill:{color}")?;
+                    writeln!(w, "    class {} {class}", ids.join(","))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the nodes of the diagram, with the kind each package is reached
+    /// through. Packages reached through several kinds of dependency get the
+    /// kind that matters most, e.g. a normal and build dependency is normal.
+    fn nodes(&self) -> BTreeMap<&'a Package, Node<'a>> {
+        let kinds = self.graph.kinds();
+        self.graph
+            .packages()
+            .enumerate()
+            .map(|(i, package)| {
+                let kind = kinds
+                    .get(package)
+                    .and_then(|kinds| kinds.first().copied())
+                    .unwrap_or(DepKind::Normal);
+                let node = Node {
+                    id: format!("n{i}"),
+                    package,
+                    kind,
+                };
+                (package, node)
+            })
+            .collect()
+    }
+
+    /// Get the nodes of each crate with more than one version, if versions
+    /// are clustered.
+    fn clusters<'n>(
+        &self,
+        nodes: &'n BTreeMap<&'a Package, Node<'a>>,
+    ) -> BTreeMap<&'a str, Vec<&'n Node<'a>>> {
+        let mut clusters = BTreeMap::<_, Vec<_>>::new();
+        if self.cluster_versions {
+            for node in nodes.values() {
+                clusters
+                    .entry(node.package.name.as_str())
+                    .or_default()
+                    .push(node);
+            }
+        }
+        clusters.retain(|_, versions| versions.len() > 1);
+        clusters
+    }
+
+    /// Get the edges between each pair of packages, with their activating
+    /// features combined.
+    fn edges(&self) -> BTreeMap<(&'a Package, &'a Package), BTreeSet<&'a str>> {
+        let mut edges = BTreeMap::<_, BTreeSet<_>>::new();
+        for edge in self.graph.edges() {
+            edges
+                .entry((&edge.from, &edge.to))
+                .or_default()
+                .extend(edge.features.iter().map(String::as_str));
+        }
+        edges
+    }
+
+    fn color(&self, kind: DepKind) -> Option<&'static str> {
+        match kind {
+            _ if !self.color_kinds => None,
+            DepKind::Normal => None,
+            DepKind::Build => Some("#fde2c4"),
+            DepKind::Development => Some("#d6e4f5"),
+        }
+    }
+}
+
+fn mermaid_node(node: &Node) -> String {
+    format!(
+        "{}[\"{} v{}\"]",
+        node.id, node.package.name, node.package.version
+    )
+}
+
+fn join(features: &BTreeSet<&str>) -> String {
+    features.iter().copied().collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{edge, package, resolved, EXAMPLE_SOURCE};
+
+    /// root -> a 1 (feature x), root -(build)-> cc, root -(dev)-> a 2
+    fn graph() -> DependencyGraph {
+        let root = package("root", "1.0.0");
+        let a1 = package("a", "1.0.0");
+        let a2 = package("a", "2.0.0");
+        let cc = package("cc", "1.0.0");
+        let mut graph = DependencyGraph::new();
+        graph.add_root(resolved(&root, EXAMPLE_SOURCE, &[]));
+        for p in [&a1, &a2, &cc] {
+            graph.add_package(resolved(p, EXAMPLE_SOURCE, &[]));
+        }
+        graph.add_edge(edge(&root, &a1, DepKind::Normal, &["x"]));
+        graph.add_edge(edge(&root, &cc, DepKind::Build, &[]));
+        graph.add_edge(edge(&root, &a2, DepKind::Development, &[]));
+        graph
+    }
+
+    fn render(diagram: Diagram, dot: bool) -> String {
+        let mut out = Vec::new();
+        if dot {
+            diagram.write_dot(&mut out).unwrap();
+        } else {
+            diagram.write_mermaid(&mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn dot() {
+        let graph = graph();
+        assert_eq!(
+            render(Diagram::new(&graph), true),
+            r#"digraph dependencies {
+    node [shape=box];
+    n0 [label="a v1.0.0"];
+    n1 [label="a v2.0.0"];
+    n2 [label="cc v1.0.0"];
+    n3 [label="root v1.0.0"];
+    n3 -> n0;
+    n3 -> n1;
+    n3 -> n2;
+}
+"#
+        );
+        assert_eq!(
+            render(
+                Diagram::new(&graph)
+                    .color_kinds()
+                    .cluster_versions()
+                    .edge_features(),
+                true
+            ),
+            r##"digraph dependencies {
+    node [shape=box];
+    n0 [label="a v1.0.0"];
+    n1 [label="a v2.0.0", style=filled, fillcolor="#d6e4f5"];
+    n2 [label="cc v1.0.0", style=filled, fillcolor="#fde2c4"];
+    n3 [label="root v1.0.0"];
+    subgraph cluster_0 {
+        label="a";
+        n0;
+        n1;
+    }
+    n3 -> n0 [label="x"];
+    n3 -> n1;
+    n3 -> n2;
+}
+"##
+        );
+    }
+
+    #[test]
+    fn mermaid() {
+        let graph = graph();
+        assert_eq!(
+            render(
+                Diagram::new(&graph)
+                    .color_kinds()
+                    .cluster_versions()
+                    .edge_features(),
+                false
+            ),
+            r#"flowchart TD
+    n2["cc v1.0.0"]
+    n3["root v1.0.0"]
+    subgraph cluster_0 ["a"]
+        n0["a v1.0.0"]
+        n1["a v2.0.0"]
+    end
+    n3 -->|"x"| n0
+    n3 --> n1
+    n3 --> n2
+    classDef build fill:#fde2c4
+    class n2 build
+    classDef dev fill:#d6e4f5
+    class n1 dev
+"#
+        );
+    }
+}
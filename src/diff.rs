@@ -0,0 +1,203 @@
+//! Differences between dependency graphs.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{DependencyGraph, Edge, Package};
+
+/// A package that was added to or removed from a graph.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageChange {
+    pub package: Package,
+    /// The edges from the packages that require it, in the graph that has
+    /// it.
+    pub dependents: Vec<Edge>,
+}
+
+/// A crate whose versions changed between two graphs, usually an upgrade.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VersionChange {
+    pub name: String,
+    /// The versions only in the old graph.
+    pub from: Vec<String>,
+    /// The versions only in the new graph.
+    pub to: Vec<String>,
+    /// The edges from the packages that require the new versions.
+    pub dependents: Vec<Edge>,
+}
+
+/// The differences between two dependency graphs, e.g. of two versions of a
+/// crate. The roots of the graphs are left out, so only their dependencies
+/// are compared.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphDiff {
+    /// Crates that are only in the new graph, and new versions of crates
+    /// whose old versions are still there.
+    pub added: Vec<PackageChange>,
+    /// Crates that are only in the old graph, and old versions of crates
+    /// whose other versions are still there.
+    pub removed: Vec<PackageChange>,
+    /// Crates that changed versions.
+    pub upgraded: Vec<VersionChange>,
+    /// Crates with more versions in the new graph than in the old one, and
+    /// more than one, with the packages that require each version.
+    pub new_duplicates: BTreeMap<String, Vec<PackageChange>>,
+}
+
+impl GraphDiff {
+    /// Compare the `old` graph with the `new` one.
+    pub fn new(old: &DependencyGraph, new: &DependencyGraph) -> Self {
+        let old_versions = versions(old);
+        let new_versions = versions(new);
+        let names = old_versions
+            .keys()
+            .chain(new_versions.keys())
+            .collect::<BTreeSet<_>>();
+
+        let mut diff = GraphDiff::default();
+        let none = BTreeSet::new();
+        for name in names {
+            let old_set = old_versions.get(name).unwrap_or(&none);
+            let new_set = new_versions.get(name).unwrap_or(&none);
+            let removed = old_set.difference(new_set).copied().collect::<Vec<_>>();
+            let added = new_set.difference(old_set).copied().collect::<Vec<_>>();
+            match (removed.is_empty(), added.is_empty()) {
+                (true, true) => {}
+                (true, false) => diff.added.extend(added.into_iter().map(|p| change(new, p))),
+                (false, true) => diff
+                    .removed
+                    .extend(removed.into_iter().map(|p| change(old, p))),
+                (false, false) => diff.upgraded.push(VersionChange {
+                    name: name.to_string(),
+                    from: removed.iter().map(|p| p.version.clone()).collect(),
+                    to: added.iter().map(|p| p.version.clone()).collect(),
+                    dependents: added
+                        .iter()
+                        .flat_map(|p| new.dependents(p))
+                        .cloned()
+                        .collect(),
+                }),
+            }
+            if new_set.len() > 1 && new_set.len() > old_set.len() {
+                diff.new_duplicates.insert(
+                    name.to_string(),
+                    new_set.iter().map(|p| change(new, p)).collect(),
+                );
+            }
+        }
+        diff
+    }
+
+    /// Whether the graphs have the same dependencies.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.upgraded.is_empty()
+    }
+}
+
+/// Get the versions of each crate in a graph, other than the roots.
+fn versions(graph: &DependencyGraph) -> BTreeMap<&str, BTreeSet<&Package>> {
+    let roots = graph.roots().collect::<BTreeSet<_>>();
+    let mut versions = BTreeMap::<_, BTreeSet<_>>::new();
+    for package in graph.packages().filter(|p| !roots.contains(p)) {
+        versions
+            .entry(package.name.as_str())
+            .or_default()
+            .insert(package);
+    }
+    versions
+}
+
+fn change(graph: &DependencyGraph, package: &Package) -> PackageChange {
+    PackageChange {
+        package: package.clone(),
+        dependents: graph.dependents(package).cloned().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{edge, package, resolved, EXAMPLE_SOURCE};
+    use crate::DepKind;
+
+    fn graph(root: &Package, edges: &[Edge]) -> DependencyGraph {
+        let mut graph = DependencyGraph::new();
+        graph.add_root(resolved(root, EXAMPLE_SOURCE, &[]));
+        for edge in edges {
+            graph.add_package(resolved(&edge.to, EXAMPLE_SOURCE, &[]));
+            graph.add_edge(edge.clone());
+        }
+        graph
+    }
+
+    #[test]
+    fn diff() {
+        let (root1, root2) = (package("root", "1.0.0"), package("root", "2.0.0"));
+        let (a1, a2) = (package("a", "1.0.0"), package("a", "2.0.0"));
+        let (b, c, d1, d2) = (
+            package("b", "1.0.0"),
+            package("c", "1.0.0"),
+            package("d", "1.0.0"),
+            package("d", "2.0.0"),
+        );
+        // root 1 -> a 1, b, d 1; root 2 -> a 2, c (feature x) -> d 2, d 1.
+        let old = graph(
+            &root1,
+            &[
+                edge(&root1, &a1, DepKind::Normal, &[]),
+                edge(&root1, &b, DepKind::Normal, &[]),
+                edge(&root1, &d1, DepKind::Normal, &[]),
+            ],
+        );
+        let new = graph(
+            &root2,
+            &[
+                edge(&root2, &a2, DepKind::Normal, &[]),
+                edge(&root2, &c, DepKind::Normal, &["x"]),
+                edge(&c, &d2, DepKind::Normal, &[]),
+                edge(&root2, &d1, DepKind::Normal, &[]),
+            ],
+        );
+
+        let diff = GraphDiff::new(&old, &new);
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.added,
+            [
+                PackageChange {
+                    package: c.clone(),
+                    dependents: vec![edge(&root2, &c, DepKind::Normal, &["x"])],
+                },
+                PackageChange {
+                    package: d2.clone(),
+                    dependents: vec![edge(&c, &d2, DepKind::Normal, &[])],
+                },
+            ]
+        );
+        assert_eq!(
+            diff.removed,
+            [PackageChange {
+                package: b.clone(),
+                dependents: vec![edge(&root1, &b, DepKind::Normal, &[])],
+            }]
+        );
+        assert_eq!(
+            diff.upgraded,
+            [VersionChange {
+                name: "a".to_string(),
+                from: vec!["1.0.0".to_string()],
+                to: vec!["2.0.0".to_string()],
+                dependents: vec![edge(&root2, &a2, DepKind::Normal, &[])],
+            }]
+        );
+        assert_eq!(
+            diff.new_duplicates.keys().collect::<Vec<_>>(),
+            [&"d".to_string()]
+        );
+        assert_eq!(diff.new_duplicates["d"].len(), 2);
+
+        assert!(GraphDiff::new(&old, &old).is_empty());
+    }
+}
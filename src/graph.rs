@@ -0,0 +1,520 @@
+//! Dependency graphs with typed edges.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Package, ResolvedPackage};
+
+/// The kind of a dependency edge.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DepKind {
+    Normal,
+    Build,
+    #[cfg_attr(feature = "serde", serde(rename = "dev"))]
+    Development,
+}
+
+impl From<cargo::core::dependency::DepKind> for DepKind {
+    fn from(kind: cargo::core::dependency::DepKind) -> Self {
+        match kind {
+            cargo::core::dependency::DepKind::Normal => DepKind::Normal,
+            cargo::core::dependency::DepKind::Build => DepKind::Build,
+            cargo::core::dependency::DepKind::Development => DepKind::Development,
+        }
+    }
+}
+
+/// A directed edge from a package to one of its dependencies.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edge {
+    pub from: Package,
+    pub to: Package,
+    /// The version requirement `from` declares for `to`.
+    pub version_req: String,
+    pub kind: DepKind,
+    pub optional: bool,
+    /// Features of `from` that activated this dependency, if it's optional,
+    /// or enabled one of its features (e.g. `to/feature`).
+    pub features: BTreeSet<String>,
+    /// The platform the dependency is restricted to, e.g. `cfg(windows)`.
+    pub target: Option<String>,
+}
+
+type EdgeKey = (Package, Package, DepKind, String, Option<String>);
+
+impl Edge {
+    fn key(&self) -> EdgeKey {
+        (
+            self.from.clone(),
+            self.to.clone(),
+            self.kind,
+            self.version_req.clone(),
+            self.target.clone(),
+        )
+    }
+}
+
+/// A resolved dependency graph.
+///
+/// Graphs from several resolutions (e.g. with different features enabled) can
+/// be merged. Edges that only differ in their activating features are
+/// combined into a single edge.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct DependencyGraph {
+    roots: BTreeSet<Package>,
+    packages: BTreeMap<Package, ResolvedPackage>,
+    edges: BTreeMap<EdgeKey, Edge>,
+}
+
+impl DependencyGraph {
+    /// Create an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The packages resolution was requested for.
+    pub fn roots(&self) -> impl Iterator<Item = &Package> {
+        self.roots.iter()
+    }
+
+    /// Every package in the graph, including the roots.
+    pub fn packages(&self) -> impl Iterator<Item = &Package> {
+        self.packages.keys()
+    }
+
+    /// Every package in the graph with its metadata, including the roots.
+    pub fn resolved_packages(&self) -> impl Iterator<Item = &ResolvedPackage> {
+        self.packages.values()
+    }
+
+    /// Get the metadata for `package`.
+    pub fn package(&self, package: &Package) -> Option<&ResolvedPackage> {
+        self.packages.get(package)
+    }
+
+    pub fn contains(&self, package: &Package) -> bool {
+        self.packages.contains_key(package)
+    }
+
+    /// Every edge in the graph.
+    pub fn edges(&self) -> impl Iterator<Item = &Edge> {
+        self.edges.values()
+    }
+
+    /// The edges from `package` to its dependencies.
+    pub fn dependencies<'a>(&'a self, package: &'a Package) -> impl Iterator<Item = &'a Edge> {
+        let start = (
+            package.clone(),
+            Package {
+                name: String::new(),
+                version: String::new(),
+            },
+            DepKind::Normal,
+            String::new(),
+            None,
+        );
+        self.edges
+            .range(start..)
+            .map(|(_, edge)| edge)
+            .take_while(move |edge| &edge.from == package)
+    }
+
+    /// The edges from packages that depend on `package`.
+    pub fn dependents<'a>(&'a self, package: &'a Package) -> impl Iterator<Item = &'a Edge> {
+        self.edges().filter(move |edge| &edge.to == package)
+    }
+
+    /// Get the kinds of dependency through which each package is reached
+    /// from the roots.
+    ///
+    /// Everything reached through a build dependency is a build dependency,
+    /// since it only runs at build time. Everything else reached through a
+    /// root's dev-dependency is a dev-dependency. The roots themselves, and
+    /// packages reached only through normal dependencies, are normal.
+    pub fn kinds(&self) -> BTreeMap<&Package, BTreeSet<DepKind>> {
+        let mut kinds: BTreeMap<&Package, BTreeSet<DepKind>> = BTreeMap::new();
+        let mut queue = self
+            .roots
+            .iter()
+            .map(|root| (root, DepKind::Normal))
+            .collect::<Vec<_>>();
+        while let Some((package, kind)) = queue.pop() {
+            if !kinds.entry(package).or_default().insert(kind) {
+                continue;
+            }
+            for edge in self.dependencies(package) {
+                let kind = match (kind, edge.kind) {
+                    (DepKind::Build, _) | (_, DepKind::Build) => DepKind::Build,
+                    (DepKind::Development, _) | (_, DepKind::Development) => DepKind::Development,
+                    (DepKind::Normal, DepKind::Normal) => DepKind::Normal,
+                };
+                queue.push((&edge.to, kind));
+            }
+        }
+        kinds
+    }
+
+    /// Get the crates with more than one version in the graph. Each version
+    /// maps to the edges from the packages that require it.
+    pub fn duplicates(&self) -> BTreeMap<&str, BTreeMap<&Package, Vec<&Edge>>> {
+        let mut versions = BTreeMap::<_, BTreeMap<_, Vec<_>>>::new();
+        for package in self.packages() {
+            versions
+                .entry(package.name.as_str())
+                .or_default()
+                .insert(package, Vec::new());
+        }
+        versions.retain(|_, versions| versions.len() > 1);
+        for edge in self.edges() {
+            if let Some(dependents) = versions
+                .get_mut(edge.to.name.as_str())
+                .and_then(|versions| versions.get_mut(&edge.to))
+            {
+                dependents.push(edge);
+            }
+        }
+        versions
+    }
+
+    /// Get every path from `from` to any version of the crate named `to`, as
+    /// the edges along the path. Paths end at the first version of `to` they
+    /// reach.
+    pub fn paths<'a>(&'a self, from: &'a Package, to: &str) -> Vec<Vec<&'a Edge>> {
+        // Only follow edges to packages that lead to `to`.
+        let mut leads_to = BTreeSet::new();
+        let mut queue = self
+            .packages()
+            .filter(|package| package.name == to)
+            .collect::<Vec<_>>();
+        while let Some(package) = queue.pop() {
+            if leads_to.insert(package) {
+                queue.extend(self.dependents(package).map(|edge| &edge.from));
+            }
+        }
+
+        let mut paths = Vec::new();
+        self.find_paths(from, to, &leads_to, &mut Vec::new(), &mut paths);
+        paths
+    }
+
+    fn find_paths<'a>(
+        &'a self,
+        package: &'a Package,
+        to: &str,
+        leads_to: &BTreeSet<&Package>,
+        path: &mut Vec<&'a Edge>,
+        paths: &mut Vec<Vec<&'a Edge>>,
+    ) {
+        for edge in self.dependencies(package) {
+            let cycle = edge.to == *package || path.iter().any(|e| e.from == edge.to);
+            if cycle || !leads_to.contains(&edge.to) {
+                continue;
+            }
+            path.push(edge);
+            if edge.to.name == to {
+                paths.push(path.clone());
+            } else {
+                self.find_paths(&edge.to, to, leads_to, path, paths);
+            }
+            path.pop();
+        }
+    }
+
+    /// Merge `other` into this graph.
+    pub fn merge(&mut self, other: DependencyGraph) {
+        self.roots.extend(other.roots);
+        for package in other.packages.into_values() {
+            self.add_package(package);
+        }
+        for edge in other.edges.into_values() {
+            self.add_edge(edge);
+        }
+    }
+
+    pub(crate) fn add_root(&mut self, package: ResolvedPackage) {
+        self.roots.insert(package.package.clone());
+        self.add_package(package);
+    }
+
+    /// Add a package, merging its enabled features if it's already present.
+    pub(crate) fn add_package(&mut self, package: ResolvedPackage) {
+        self.packages
+            .entry(package.package.clone())
+            .and_modify(|p| p.features.extend(package.features.iter().cloned()))
+            .or_insert(package);
+    }
+
+    /// Add an edge. Both of its packages must be added separately.
+    pub(crate) fn add_edge(&mut self, edge: Edge) {
+        self.edges
+            .entry(edge.key())
+            .and_modify(|e| e.features.extend(edge.features.iter().cloned()))
+            .or_insert(edge);
+    }
+}
+
+/// The serialized form of a [`DependencyGraph`]: its roots, packages and
+/// edges as lists.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct GraphRepr<P, E> {
+    roots: Vec<Package>,
+    packages: Vec<P>,
+    edges: Vec<E>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DependencyGraph {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        GraphRepr {
+            roots: self.roots.iter().cloned().collect(),
+            packages: self.resolved_packages().collect(),
+            edges: self.edges().collect(),
+        }
+        .serialize(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DependencyGraph {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let repr = GraphRepr::<ResolvedPackage, Edge>::deserialize(d)?;
+        let mut graph = DependencyGraph::new();
+        for package in repr.packages {
+            graph.add_package(package);
+        }
+        for root in repr.roots {
+            if !graph.contains(&root) {
+                return Err(serde::de::Error::custom(format!(
+                    "root {} {} is not a package in the graph",
+                    root.name, root.version
+                )));
+            }
+            graph.roots.insert(root);
+        }
+        for edge in repr.edges {
+            graph.add_edge(edge);
+        }
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{edge, package, resolved, EXAMPLE_SOURCE};
+
+    #[test]
+    fn merge_combines_edge_features() {
+        let root = package("root", "1.0.0");
+        let a = package("a", "1.0.0");
+        let b = package("b", "1.0.0");
+
+        let mut graph = DependencyGraph::new();
+        graph.add_root(resolved(&root, EXAMPLE_SOURCE, &["x"]));
+        graph.add_package(resolved(&a, EXAMPLE_SOURCE, &["std"]));
+        graph.add_edge(edge(&root, &a, DepKind::Normal, &["x"]));
+
+        let mut other = DependencyGraph::new();
+        other.add_root(resolved(&root, EXAMPLE_SOURCE, &["y"]));
+        other.add_package(resolved(&a, EXAMPLE_SOURCE, &["alloc"]));
+        other.add_package(resolved(&b, EXAMPLE_SOURCE, &[]));
+        other.add_edge(edge(&root, &a, DepKind::Normal, &["y"]));
+        other.add_edge(edge(&a, &b, DepKind::Normal, &[]));
+        graph.merge(other);
+
+        assert_eq!(graph.roots().collect::<Vec<_>>(), [&root]);
+        assert_eq!(graph.packages().count(), 3);
+        assert_eq!(
+            graph.package(&a).unwrap().features,
+            BTreeSet::from(["alloc".to_string(), "std".to_string()])
+        );
+        assert_eq!(graph.edges().count(), 2);
+
+        let deps = graph.dependencies(&root).collect::<Vec<_>>();
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].to, a);
+        assert_eq!(
+            deps[0].features,
+            BTreeSet::from(["x".to_string(), "y".to_string()])
+        );
+
+        let dependents = graph.dependents(&b).collect::<Vec<_>>();
+        assert_eq!(dependents.len(), 1);
+        assert_eq!(dependents[0].from, a);
+        assert_eq!(graph.dependencies(&b).count(), 0);
+    }
+
+    #[test]
+    fn kinds_propagate() {
+        let root = package("root", "1.0.0");
+        let shared = package("shared", "1.0.0");
+        let cc = package("cc", "1.0.0");
+        let jobs = package("jobs", "1.0.0");
+        let test_util = package("test-util", "1.0.0");
+        let test_dep = package("test-dep", "1.0.0");
+
+        let mut graph = DependencyGraph::new();
+        graph.add_root(resolved(&root, EXAMPLE_SOURCE, &[]));
+        for package in [&shared, &cc, &jobs, &test_util, &test_dep] {
+            graph.add_package(resolved(package, EXAMPLE_SOURCE, &[]));
+        }
+        graph.add_edge(edge(&root, &shared, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root, &cc, DepKind::Build, &[]));
+        graph.add_edge(edge(&cc, &jobs, DepKind::Normal, &[]));
+        graph.add_edge(edge(&cc, &shared, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root, &test_util, DepKind::Development, &[]));
+        graph.add_edge(edge(&test_util, &test_dep, DepKind::Normal, &[]));
+        graph.add_edge(edge(&test_util, &cc, DepKind::Build, &[]));
+
+        let kinds = graph.kinds();
+        let kinds = |p: &Package| kinds[p].iter().copied().collect::<Vec<_>>();
+        assert_eq!(kinds(&root), [DepKind::Normal]);
+        assert_eq!(kinds(&shared), [DepKind::Normal, DepKind::Build]);
+        assert_eq!(kinds(&cc), [DepKind::Build]);
+        assert_eq!(kinds(&jobs), [DepKind::Build]);
+        assert_eq!(kinds(&test_util), [DepKind::Development]);
+        assert_eq!(kinds(&test_dep), [DepKind::Development]);
+    }
+
+    #[test]
+    fn duplicates() {
+        let root = package("root", "1.0.0");
+        let a = package("a", "1.0.0");
+        let b1 = package("b", "1.0.0");
+        let b2 = package("b", "2.0.0");
+
+        let mut graph = DependencyGraph::new();
+        graph.add_root(resolved(&root, EXAMPLE_SOURCE, &[]));
+        for p in [&a, &b1, &b2] {
+            graph.add_package(resolved(p, EXAMPLE_SOURCE, &[]));
+        }
+        graph.add_edge(edge(&root, &a, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root, &b2, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root, &b1, DepKind::Build, &[]));
+        graph.add_edge(edge(&a, &b1, DepKind::Normal, &[]));
+
+        let duplicates = graph.duplicates();
+        assert_eq!(duplicates.keys().collect::<Vec<_>>(), [&"b"]);
+        let dependents = duplicates["b"]
+            .iter()
+            .map(|(version, edges)| {
+                let edges = edges
+                    .iter()
+                    .map(|e| (e.from.name.as_str(), e.kind))
+                    .collect::<Vec<_>>();
+                (version.version.as_str(), edges)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dependents,
+            [
+                (
+                    "1.0.0",
+                    vec![("a", DepKind::Normal), ("root", DepKind::Build)]
+                ),
+                ("2.0.0", vec![("root", DepKind::Normal)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn paths() {
+        let root = package("root", "1.0.0");
+        let a = package("a", "1.0.0");
+        let b = package("b", "1.0.0");
+        let c1 = package("c", "1.0.0");
+        let c2 = package("c", "2.0.0");
+        let d = package("d", "1.0.0");
+
+        let mut graph = DependencyGraph::new();
+        graph.add_root(resolved(&root, EXAMPLE_SOURCE, &[]));
+        for p in [&a, &b, &c1, &c2, &d] {
+            graph.add_package(resolved(p, EXAMPLE_SOURCE, &[]));
+        }
+        graph.add_edge(edge(&root, &a, DepKind::Normal, &["x"]));
+        graph.add_edge(edge(&root, &b, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root, &d, DepKind::Normal, &[]));
+        graph.add_edge(edge(&a, &c1, DepKind::Normal, &[]));
+        graph.add_edge(edge(&b, &a, DepKind::Normal, &[]));
+        graph.add_edge(edge(&b, &c2, DepKind::Normal, &[]));
+        graph.add_edge(edge(&c1, &c2, DepKind::Normal, &[]));
+
+        let paths = graph
+            .paths(&root, "c")
+            .into_iter()
+            .map(|path| {
+                path.iter()
+                    .map(|e| format!("{} -> {} {}", e.from.name, e.to.name, e.to.version))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                vec!["root -> a 1.0.0", "a -> c 1.0.0"],
+                vec!["root -> b 1.0.0", "b -> a 1.0.0", "a -> c 1.0.0"],
+                vec!["root -> b 1.0.0", "b -> c 2.0.0"],
+            ]
+        );
+        assert_eq!(
+            graph.paths(&root, "a")[0][0].features,
+            BTreeSet::from(["x".to_string()])
+        );
+        assert!(graph.paths(&root, "missing").is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_schema() {
+        let root = package("root", "1.0.0");
+        let a = package("a", "1.0.0");
+        let mut graph = DependencyGraph::new();
+        graph.add_root(resolved(&root, EXAMPLE_SOURCE, &["x"]));
+        graph.add_package(resolved(&a, EXAMPLE_SOURCE, &[]));
+        graph.add_edge(edge(&root, &a, DepKind::Development, &[]));
+
+        let json = serde_json::to_value(&graph).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "roots": [{"name": "root", "version": "1.0.0"}],
+                "packages": [
+                    {
+                        "name": "a",
+                        "version": "1.0.0",
+                        "source": "registry+https://example.com/index",
+                        "checksum": format!("{:0>64}", "a"),
+                        "features": [],
+                        "links": null,
+                        "rust_version": null,
+                    },
+                    {
+                        "name": "root",
+                        "version": "1.0.0",
+                        "source": "registry+https://example.com/index",
+                        "checksum": format!("{:0>64}", "root"),
+                        "features": ["x"],
+                        "links": null,
+                        "rust_version": null,
+                    },
+                ],
+                "edges": [{
+                    "from": {"name": "root", "version": "1.0.0"},
+                    "to": {"name": "a", "version": "1.0.0"},
+                    "version_req": "^1.0.0",
+                    "kind": "dev",
+                    "optional": false,
+                    "features": [],
+                    "target": null,
+                }],
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<DependencyGraph>(json).unwrap(),
+            graph
+        );
+    }
+}
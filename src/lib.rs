@@ -1,29 +1,59 @@
 #![doc = include_str!("../README.md")]
 
-// TODO
-//  * Use a local registry index
-//  * Should we be specifying a config (it determines where warnings are printed)?
-
-use std::collections::BTreeMap;
 use std::collections::HashSet;
-use std::mem::ManuallyDrop;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use anyhow::Error as CargoError;
+use anyhow::{anyhow, Error as CargoError};
+use cargo::core::dependency::DepKind as CargoDepKind;
 use cargo::core::package_id::PackageId;
 use cargo::core::registry::{PackageRegistry, Registry};
 use cargo::core::resolver::features::RequestedFeatures;
-use cargo::core::resolver::{self, CliFeatures, ResolveOpts, VersionOrdering, VersionPreferences};
+use cargo::core::resolver::{
+    self, CliFeatures, HasDevUnits, Resolve, ResolveOpts, VersionOrdering, VersionPreferences,
+};
 use cargo::core::summary::Summary;
-use cargo::core::SourceId;
 use cargo::core::{Dependency, FeatureValue};
+use cargo::core::{PackageSet, Shell, SourceId, Workspace};
+use cargo::ops;
 use cargo::sources::source::QueryKind;
 use cargo::sources::IndexSummary;
 use cargo::util::cache_lock::CacheLockMode;
-use cargo::util::config::Config;
+use cargo::util::config::homedir;
 use cargo::util::interning::InternedString;
-use cargo::util::OptVersionReq;
+use cargo::util::{IntoUrl, OptVersionReq};
 use thiserror::Error;
 
+mod cyclonedx;
+mod diagram;
+mod diff;
+mod graph;
+mod lockfile;
+mod parallel;
+mod purl;
+mod request;
+#[cfg(feature = "serde")]
+mod ser;
+mod spdx;
+mod target;
+#[cfg(test)]
+mod testing;
+mod tree;
+
+pub use cargo::util::config::Config;
+pub use cyclonedx::CycloneDx;
+pub use diagram::Diagram;
+pub use diff::{GraphDiff, PackageChange, VersionChange};
+pub use graph::{DepKind, DependencyGraph, Edge};
+pub use lockfile::LockfileVersion;
+pub use request::{FeatureSelection, ResolveRequest, WorkspaceRequest};
+pub use spdx::Spdx;
+pub use target::builtin_targets;
+pub use tree::Tree;
+
+use target::{parse_cfg, Platforms, Target, DEFAULT_HOST};
+
 const DUMMY_PACKAGE_NAME: &str = "dummy-pkg";
 const DUMMY_PACKAGE_VERSION: semver::Version = semver::Version {
     major: 0,
@@ -35,53 +65,455 @@ const DUMMY_PACKAGE_VERSION: semver::Version = semver::Version {
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("cargo error {0:?}")]
+    #[error(transparent)]
     CargoError(#[from] CargoError),
     #[error("couldn't find package: {name} ({version:?})")]
     PackageNotFound {
         name: String,
         version: Option<String>,
     },
+    #[error("not a local registry (missing index directory): {0}")]
+    InvalidRegistry(String),
+    #[error("unknown target: {0}")]
+    UnknownTarget(String),
+    #[error("invalid cfg: {0}")]
+    InvalidCfg(String),
+    #[error("invalid lockfile: {0}")]
+    InvalidLockfile(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// A dependency graph and the features whose dependencies couldn't be
+/// resolved.
+pub type Resolution = (DependencyGraph, Vec<UnresolvedFeature>);
+
 /// A package dependency.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Package {
     pub name: String,
     pub version: String,
 }
 
+impl From<PackageId> for Package {
+    fn from(pkg_id: PackageId) -> Self {
+        Self {
+            name: pkg_id.name().to_string(),
+            version: pkg_id.version().to_string(),
+        }
+    }
+}
+
+/// The sources of crates.io packages, through its git and sparse indexes.
+pub(crate) const CRATES_IO_SOURCES: [&str; 2] = [
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+/// A resolved package with the metadata from its registry index entry.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResolvedPackage {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub package: Package,
+    /// The source the package was resolved from, in `Cargo.lock` format (e.g.
+    /// `registry+https://github.com/rust-lang/crates.io-index`).
+    pub source: String,
+    /// The SHA-256 checksum of the package's `.crate` file.
+    pub checksum: Option<String>,
+    /// The features enabled on the package.
+    pub features: BTreeSet<String>,
+    /// The native library the package links to.
+    pub links: Option<String>,
+    /// The minimum supported Rust version declared by the package.
+    pub rust_version: Option<String>,
+}
+
+impl ResolvedPackage {
+    fn new(resolve: &Resolve, pkg_id: PackageId) -> Self {
+        let summary = resolve.summary(pkg_id);
+        Self {
+            package: Package::from(pkg_id),
+            source: pkg_id.source_id().as_url().to_string(),
+            checksum: summary.checksum().map(str::to_string),
+            features: resolve
+                .features(pkg_id)
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+            links: summary.links().map(|l| l.to_string()),
+            rust_version: summary.rust_version().map(|v| v.to_string()),
+        }
+    }
+
+    /// Whether the package is from crates.io.
+    pub(crate) fn is_crates_io(&self) -> bool {
+        CRATES_IO_SOURCES.contains(&self.source.as_str())
+    }
+}
+
+/// The dependencies of a package, attributed to the features that add them.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeatureDependencies {
+    /// The dependencies required with no features enabled, including the
+    /// package itself.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::sorted"))]
+    pub baseline: HashSet<Package>,
+    /// The dependencies each feature adds to the baseline when enabled on its
+    /// own.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::sorted_values"))]
+    pub features: BTreeMap<String, HashSet<Package>>,
+}
+
+/// The combined result of resolving many requests with
+/// [`Resolver::resolve_many`] or [`ResolverBuilder::resolve_parallel`].
+#[derive(Debug, Default)]
+pub struct BatchResolution {
+    /// The dependency graphs of every request that could be resolved.
+    pub graph: DependencyGraph,
+    /// The roots whose dependency graphs include each package, including the
+    /// roots themselves.
+    pub required_by: BTreeMap<Package, BTreeSet<Package>>,
+    /// The result of each request, in order: the features whose dependencies
+    /// couldn't be resolved, or the error that stopped it from being resolved.
+    pub results: Vec<Result<Vec<UnresolvedFeature>>>,
+}
+
+impl BatchResolution {
+    /// Add the result of the next request, merging its graph if it could be
+    /// resolved.
+    fn push(&mut self, graph: DependencyGraph, result: Result<Vec<UnresolvedFeature>>) {
+        if result.is_ok() {
+            for root in graph.roots() {
+                for package in graph.packages() {
+                    self.required_by
+                        .entry(package.clone())
+                        .or_default()
+                        .insert(root.clone());
+                }
+            }
+            self.graph.merge(graph);
+        }
+        self.results.push(result);
+    }
+}
+
 /// A feature that could not be toggled for dependency resolution.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct UnresolvedFeature {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::error"))]
     pub error: Error,
 }
 
 /// A package dependency resolver.
-pub struct Resolver {
-    config: ManuallyDrop<Box<Config>>,
-    registry: ManuallyDrop<PackageRegistry<'static>>,
+///
+/// A resolver borrows a Cargo [`Config`], which determines where the Cargo
+/// home is and where warnings are printed. It keeps one registry for its
+/// whole lifetime, so the registry index is only read (or fetched) once, and
+/// later calls reuse it.
+///
+/// Resolvers aren't `Send`, since Cargo's config isn't thread-safe, and
+/// resolution holds Cargo's package cache lock, which only one resolver
+/// sharing a Cargo home can hold at a time. To resolve many packages, use
+/// [`resolve_many`](Self::resolve_many), which takes the lock once, or
+/// [`ResolverBuilder::resolve_parallel`], which resolves them on several
+/// threads.
+pub struct Resolver<'cfg> {
+    config: &'cfg Config,
+    registry: Box<dyn Registry + 'cfg>,
     source: SourceId,
+    platforms: Platforms,
+    versions: Versions,
 }
 
-impl Resolver {
-    /// Create a new package dependency resolver using the current Cargo config
-    /// and the crates.io index.
-    pub fn new() -> Result<Self> {
-        let config = Box::new(Config::default()?);
-        let source = SourceId::crates_io(&config)?;
-        let mut registry = PackageRegistry::new(unsafe { std::mem::transmute(&*config) })?;
+/// How the versions of dependencies are chosen.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum VersionStrategy {
+    /// The newest compatible versions, like Cargo normally does.
+    #[default]
+    Maximum,
+    /// The oldest compatible versions, like `cargo -Z minimal-versions`.
+    Minimal,
+    /// The oldest compatible versions of the resolved package's direct
+    /// dependencies, and the newest versions of everything else, like `cargo
+    /// -Z direct-minimal-versions`.
+    DirectMinimal,
+}
+
+/// The registry index a [`Resolver`] queries.
+#[derive(Clone, Debug, Default)]
+enum IndexSource {
+    #[default]
+    CratesIo,
+    Named(String),
+    Url(String),
+    Local(PathBuf),
+}
+
+/// A builder for configuring a [`Resolver`].
+#[derive(Clone, Debug, Default)]
+pub struct ResolverBuilder {
+    index: IndexSource,
+    cwd: Option<PathBuf>,
+    cargo_home: Option<PathBuf>,
+    targets: Vec<String>,
+    host: Option<String>,
+    cfgs: Vec<String>,
+    lockfile: Option<PathBuf>,
+    locked: Vec<(Package, String)>,
+    version_strategy: VersionStrategy,
+}
+
+impl ResolverBuilder {
+    /// Create a builder for a resolver that uses the current Cargo config and
+    /// the crates.io index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve against a registry by name, as configured in the `registries`
+    /// table of the Cargo config.
+    pub fn registry(mut self, name: &str) -> Self {
+        self.index = IndexSource::Named(name.to_string());
+        self
+    }
+
+    /// Resolve against the registry index at `url`. Sparse indexes are
+    /// selected with a `sparse+` prefix (e.g. `sparse+https://...`).
+    pub fn index_url(mut self, url: &str) -> Self {
+        self.index = IndexSource::Url(url.to_string());
+        self
+    }
+
+    /// Resolve against a local registry created by `cargo local-registry`.
+    pub fn local_registry<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.index = IndexSource::Local(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Load the Cargo config (see [`config`](Self::config)) as if cargo were
+    /// run from `cwd`, instead of the current directory.
+    pub fn cwd<P: AsRef<Path>>(mut self, cwd: P) -> Self {
+        self.cwd = Some(cwd.as_ref().to_path_buf());
+        self
+    }
+
+    /// Use `cargo_home` for the Cargo home directory (global config and
+    /// registry caches) of the config (see [`config`](Self::config)), instead
+    /// of `$CARGO_HOME`.
+    pub fn cargo_home<P: AsRef<Path>>(mut self, cargo_home: P) -> Self {
+        self.cargo_home = Some(cargo_home.as_ref().to_path_buf());
+        self
+    }
+
+    /// Only include dependencies that apply to the target `triple`, like
+    /// `cargo tree --target`. May be given more than once to include the
+    /// dependencies of several targets. By default, dependencies for every
+    /// platform are included.
+    ///
+    /// Like Cargo, build dependencies and proc-macros, and their own
+    /// dependencies, are evaluated against the host instead (see
+    /// [`host`](Self::host)). Only their manifests say which packages are
+    /// proc-macros, so the resolved packages are downloaded to Cargo's
+    /// package cache, like `cargo tree` does.
+    ///
+    /// Targets are evaluated offline against built-in cfg tables; see
+    /// [`builtin_targets`].
+    pub fn target(mut self, triple: &str) -> Self {
+        self.targets.push(triple.to_string());
+        self
+    }
+
+    /// Evaluate the dependencies built for the host (see
+    /// [`target`](Self::target)) against the target `triple`, instead of the
+    /// one this crate was built for. The extra cfgs set with
+    /// [`cfg`](Self::cfg) don't apply to the host, like `RUSTFLAGS` with
+    /// `cargo --target`.
+    pub fn host(mut self, triple: &str) -> Self {
+        self.host = Some(triple.to_string());
+        self
+    }
+
+    /// Set an additional cfg for every target, e.g. `tokio_unstable` or
+    /// `feature="foo"`, like passing `--cfg` in `RUSTFLAGS`.
+    pub fn cfg(mut self, cfg: &str) -> Self {
+        self.cfgs.push(cfg.to_string());
+        self
+    }
+
+    /// Prefer the versions pinned by the `Cargo.lock` file at `path`, like
+    /// Cargo does when a lockfile exists. Versions that don't satisfy the
+    /// requirements being resolved are still replaced.
+    ///
+    /// Packages locked to any registry are also matched in this resolver's
+    /// registry, so a lockfile generated against crates.io applies to a
+    /// mirror of it. A relative `path` is relative to the config's working
+    /// directory.
+    pub fn lockfile<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.lockfile = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Prefer the versions of the packages in a previously resolved `graph`,
+    /// in the same way as [`lockfile`](Self::lockfile).
+    pub fn previous(mut self, graph: &DependencyGraph) -> Self {
+        self.locked.extend(
+            graph
+                .resolved_packages()
+                .map(|p| (p.package.clone(), p.source.clone())),
+        );
+        self
+    }
+
+    /// Choose the versions of dependencies with `strategy`, e.g. to check that
+    /// the lower bounds of a package's requirements resolve. The requested
+    /// package itself is still the newest version that matches the request.
+    ///
+    /// Locked versions (see [`lockfile`](Self::lockfile)) are preferred over
+    /// the strategy.
+    pub fn version_strategy(mut self, strategy: VersionStrategy) -> Self {
+        self.version_strategy = strategy;
+        self
+    }
+
+    /// Create the resolver, borrowing `config`. Relative paths are resolved
+    /// against its working directory.
+    pub fn build(self, config: &Config) -> Result<Resolver<'_>> {
+        let mut registry = PackageRegistry::new(config)?;
         registry.lock_patches();
-        Ok(Self {
-            config: ManuallyDrop::new(config),
-            registry: ManuallyDrop::new(registry),
+        self.build_with_registry(config, Box::new(registry))
+    }
+
+    /// Create the resolver with another registry than Cargo's, e.g. an
+    /// in-memory one. The registry must serve the builder's index.
+    pub(crate) fn build_with_registry<'cfg>(
+        self,
+        config: &'cfg Config,
+        registry: Box<dyn Registry + 'cfg>,
+    ) -> Result<Resolver<'cfg>> {
+        let cfgs = self
+            .cfgs
+            .iter()
+            .map(|cfg| parse_cfg(cfg))
+            .collect::<Result<Vec<_>>>()?;
+        let targets = self
+            .targets
+            .iter()
+            .map(|triple| Target::new(triple, &cfgs))
+            .collect::<Result<Vec<_>>>()?;
+
+        let source = match &self.index {
+            IndexSource::CratesIo => SourceId::crates_io(config)?,
+            IndexSource::Named(name) => SourceId::alt_registry(config, name)?,
+            IndexSource::Url(url) => SourceId::for_registry(&url.into_url()?)?,
+            IndexSource::Local(path) => {
+                // Relative paths are relative to the config's working
+                // directory, which Cargo needs as an absolute URL.
+                let absolute = config.cwd().join(path);
+                if !absolute.join("index").is_dir() {
+                    return Err(Error::InvalidRegistry(path.display().to_string()));
+                }
+                SourceId::for_local_registry(&absolute)?
+            }
+        };
+        let mut locked = self.locked;
+        if let Some(path) = &self.lockfile {
+            let contents = fs::read_to_string(config.cwd().join(path))
+                .map_err(|e| anyhow!("couldn't read {}: {e}", path.display()))?;
+            locked.extend(lockfile::decode(&contents)?);
+        }
+        let mut versions = Versions {
+            strategy: self.version_strategy,
+            locked: Vec::new(),
+        };
+        for (package, package_source) in &locked {
+            versions
+                .locked
+                .extend(locked_package_ids(package, package_source, source)?);
+        }
+
+        let platforms = if targets.is_empty() {
+            Platforms::default()
+        } else {
+            let host = self.host.as_deref().unwrap_or(DEFAULT_HOST);
+            Platforms::new(targets, Target::new(host, &[])?)
+        };
+
+        Ok(Resolver {
+            config,
+            registry,
             source,
+            platforms,
+            versions,
         })
     }
 
+    /// Load the Cargo config for the resolver, from the directory given to
+    /// [`cwd`](Self::cwd) and the Cargo home given to
+    /// [`cargo_home`](Self::cargo_home), or else from the current ones. It
+    /// must outlive the resolver built with it.
+    pub fn config(&self) -> Result<Config> {
+        if self.cwd.is_none() && self.cargo_home.is_none() {
+            return Ok(Config::default()?);
+        }
+
+        let cwd = match &self.cwd {
+            Some(cwd) => cwd.clone(),
+            None => std::env::current_dir().map_err(CargoError::from)?,
+        };
+        let cargo_home = match &self.cargo_home {
+            Some(cargo_home) => cargo_home.clone(),
+            None => {
+                homedir(&cwd).ok_or_else(|| anyhow!("couldn't find the Cargo home directory"))?
+            }
+        };
+        Ok(Config::new(Shell::new(), cwd, cargo_home))
+    }
+
+    /// Resolve many requests like [`Resolver::resolve_many`], on `threads`
+    /// worker threads. Each worker builds its own resolver, with its own
+    /// config (see [`config`](Self::config)).
+    ///
+    /// The registry index files the requests may need are first fetched to
+    /// the index cache, which the workers then share without updating it.
+    /// Workers only take turns while reading the index, and are quiet, so
+    /// Cargo's warnings aren't printed.
+    pub fn resolve_parallel(
+        &self,
+        requests: &[ResolveRequest],
+        threads: usize,
+    ) -> Result<BatchResolution> {
+        parallel::resolve_parallel(self, requests, threads)
+    }
+}
+
+impl<'cfg> Resolver<'cfg> {
+    /// Create a new package dependency resolver using the Cargo `config`
+    /// (e.g. [`Config::default`]) and the crates.io index.
+    pub fn new(config: &'cfg Config) -> Result<Self> {
+        ResolverBuilder::new().build(config)
+    }
+
+    /// Create a new package dependency resolver using the Cargo `config` and
+    /// a local registry index.
+    ///
+    /// The registry at `path` is expected to use the layout produced by
+    /// `cargo local-registry`: an `index` directory alongside the `.crate`
+    /// files it describes. No network access is required.
+    pub fn with_local_registry<P: AsRef<Path>>(config: &'cfg Config, path: P) -> Result<Self> {
+        ResolverBuilder::new().local_registry(path).build(config)
+    }
+
+    /// Create a builder to configure a resolver, e.g. to use an alternative
+    /// registry.
+    pub fn builder() -> ResolverBuilder {
+        ResolverBuilder::new()
+    }
+
     /// Get the dependencies for a single package.
     pub fn dependencies(
         &mut self,
@@ -101,74 +533,479 @@ impl Resolver {
         version: Option<&str>,
         dependencies: &mut HashSet<Package>,
     ) -> Result<Vec<UnresolvedFeature>> {
-        let dep = Dependency::parse(package, version, self.source)?;
+        let mut graph = DependencyGraph::new();
+        let unresolved_features = self.merge_dependency_graph(package, version, &mut graph)?;
+        dependencies.extend(graph.packages().cloned());
+        Ok(unresolved_features)
+    }
+
+    /// Get the dependency graph for a single package.
+    pub fn dependency_graph(
+        &mut self,
+        package: &str,
+        version: Option<&str>,
+    ) -> Result<(DependencyGraph, Vec<UnresolvedFeature>)> {
+        let mut graph = DependencyGraph::new();
+        let unresolved_features = self.merge_dependency_graph(package, version, &mut graph)?;
+        Ok((graph, unresolved_features))
+    }
+
+    /// Get the dependency graph for a single package, merging it into the
+    /// specified `graph`.
+    pub fn merge_dependency_graph(
+        &mut self,
+        package: &str,
+        version: Option<&str>,
+        graph: &mut DependencyGraph,
+    ) -> Result<Vec<UnresolvedFeature>> {
+        self.merge_request(&ResolveRequest::new(package, version), graph)
+    }
+
+    /// Get the dependency graph for a package with the requested features.
+    pub fn resolve(
+        &mut self,
+        request: &ResolveRequest,
+    ) -> Result<(DependencyGraph, Vec<UnresolvedFeature>)> {
+        let mut graph = DependencyGraph::new();
+        let unresolved_features = self.merge_request(request, &mut graph)?;
+        Ok((graph, unresolved_features))
+    }
+
+    /// Get the dependency graph for a package with the requested features,
+    /// merging it into the specified `graph`.
+    pub fn merge_request(
+        &mut self,
+        request: &ResolveRequest,
+        graph: &mut DependencyGraph,
+    ) -> Result<Vec<UnresolvedFeature>> {
+        let _lock = self
+            .config
+            .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        request_dependencies(
+            self.config,
+            self.source,
+            &mut *self.registry,
+            &self.versions,
+            &mut self.platforms,
+            request,
+            graph,
+        )
+    }
+
+    /// Get the dependency graphs for many packages at once, combined into one
+    /// graph. The package cache is locked once for the whole batch, and the
+    /// registry is only queried once for each package.
+    ///
+    /// A request that can't be resolved doesn't stop the others; its error is
+    /// in [`BatchResolution::results`].
+    pub fn resolve_many(&mut self, requests: &[ResolveRequest]) -> Result<BatchResolution> {
+        let _lock = self
+            .config
+            .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        let mut batch = BatchResolution::default();
+        for request in requests {
+            let mut graph = DependencyGraph::new();
+            let result = request_dependencies(
+                self.config,
+                self.source,
+                &mut *self.registry,
+                &self.versions,
+                &mut self.platforms,
+                request,
+                &mut graph,
+            );
+            batch.push(graph, result);
+        }
+        Ok(batch)
+    }
+
+    /// Get the dependency graph of every release of a package that matches
+    /// the request's version requirement (e.g. every `1.*` release), by
+    /// version. Releases whose dependencies can't be resolved map to the
+    /// error.
+    pub fn resolve_versions(
+        &mut self,
+        request: &ResolveRequest,
+    ) -> Result<BTreeMap<semver::Version, Result<Resolution>>> {
+        let dep = Dependency::parse(&request.package, request.version.as_deref(), self.source)?;
+        let _lock = self
+            .config
+            .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        let summaries = query_summaries(&mut *self.registry, &dep)?;
+        if summaries.is_empty() {
+            return Err(package_not_found(&dep));
+        }
+
+        let mut versions = BTreeMap::new();
+        for summary in summaries {
+            let version = summary.version().clone();
+            let mut request = request.clone();
+            request.version = Some(format!("={version}"));
+            let mut graph = DependencyGraph::new();
+            let result = request_dependencies(
+                self.config,
+                self.source,
+                &mut *self.registry,
+                &self.versions,
+                &mut self.platforms,
+                &request,
+                &mut graph,
+            );
+            versions.insert(version, result.map(|features| (graph, features)));
+        }
+        Ok(versions)
+    }
+
+    /// Get the dependency graph of the members of a local workspace, with
+    /// their declared dependencies, features and patches. The members are the
+    /// roots of the graph.
+    ///
+    /// Dependencies are looked up in the registries the manifests name, with
+    /// any source replacement from the Cargo configuration, rather than in
+    /// this resolver's registry. The versions pinned by the workspace's
+    /// `Cargo.lock` are preferred, like Cargo does; a lockfile or
+    /// [`VersionStrategy`] given to the [`ResolverBuilder`] isn't used.
+    pub fn resolve_workspace(&mut self, request: &WorkspaceRequest) -> Result<DependencyGraph> {
+        let mut graph = DependencyGraph::new();
+        self.merge_workspace(request, &mut graph)?;
+        Ok(graph)
+    }
+
+    /// Get the dependency graph of the members of a local workspace, merging
+    /// it into the specified `graph`.
+    pub fn merge_workspace(
+        &mut self,
+        request: &WorkspaceRequest,
+        graph: &mut DependencyGraph,
+    ) -> Result<()> {
+        let mut manifest_path = self.config.cwd().join(&request.manifest_path);
+        if manifest_path.is_dir() {
+            manifest_path.push("Cargo.toml");
+        }
+        let mut ws = Workspace::new(&manifest_path, self.config)?;
+        // Despite its name, this only controls whether the members'
+        // dev-dependencies are resolved.
+        ws.set_require_optional_deps(request.dev_dependencies);
+        let cli_features = CliFeatures::from_command_line(
+            &request.features,
+            request.all_features,
+            request.default_features,
+        )?;
+        let has_dev_units = if request.dev_dependencies {
+            HasDevUnits::Yes
+        } else {
+            HasDevUnits::No
+        };
+        let members = ws.members().map(|p| p.package_id()).collect::<Vec<_>>();
+        let specs = members.iter().map(|id| id.to_spec()).collect::<Vec<_>>();
+
+        let previous = if request.lockfile {
+            ops::load_pkg_lockfile(&ws)?
+        } else {
+            None
+        };
+
+        // The workspace's own patches and source replacements go into a
+        // separate registry, rather than the resolver's.
+        let mut registry = PackageRegistry::new(self.config)?;
+        let result = ops::resolve_with_previous(
+            &mut registry,
+            &ws,
+            &cli_features,
+            has_dev_units,
+            previous.as_ref(),
+            None,
+            &specs,
+            true,
+            ws.rust_version(),
+        )?;
+        self.platforms
+            .load_proc_macros(self.config, result.iter())?;
+        add_resolve(
+            &result,
+            &members,
+            &[],
+            &self.platforms,
+            request.build_dependencies,
+            graph,
+        );
+        Ok(())
+    }
+
+    /// Generate a `Cargo.lock` file that pins the resolved dependencies of a
+    /// package, with their checksums. The package itself is listed like a
+    /// workspace member, so the lockfile can be used to build it from its
+    /// source, e.g. after vendoring it.
+    ///
+    /// Like Cargo does for a workspace member, the lockfile covers every
+    /// target and every feature, and includes build and dev-dependencies, so
+    /// only the request's package and version are used. Packages from a local
+    /// registry are listed as coming from crates.io.
+    pub fn lockfile(
+        &mut self,
+        request: &ResolveRequest,
+        version: LockfileVersion,
+    ) -> Result<String> {
+        let request = ResolveRequest {
+            features: FeatureSelection::AllFeatures,
+            build_dependencies: true,
+            dev_dependencies: true,
+            ..request.clone()
+        };
+        let _lock = self
+            .config
+            .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        let (mut query, summary) =
+            request_query(&mut *self.registry, &self.versions, self.source, &request)?;
+        query.dep.set_features(summary.features().keys().copied());
+
+        let (mut resolve, dummy) = resolve_query(
+            self.config,
+            self.source,
+            &mut *self.registry,
+            &self.versions,
+            &query,
+        )?;
+        let root = resolve
+            .deps(dummy)
+            .find(|(_, deps)| deps.iter().any(|d| d.kind() != CargoDepKind::Development))
+            .map(|(root, _)| root)
+            .ok_or_else(|| Error::PackageNotFound {
+                name: request.package.clone(),
+                version: request.version.clone(),
+            })?;
+        lockfile::encode(&mut resolve, dummy, root, version)
+    }
+
+    /// Get the licenses the packages in a graph declare in their manifests,
+    /// as SPDX license expressions, e.g. to pass to [`Spdx::license`] or
+    /// [`CycloneDx::license`]. Packages without a `license` field are left
+    /// out.
+    ///
+    /// The registry index doesn't record licenses, so the packages are
+    /// downloaded to Cargo's package cache, like `cargo fetch`. Packages from
+    /// a local registry are unpacked from the `.crate` files already on disk.
+    pub fn licenses(&mut self, graph: &DependencyGraph) -> Result<BTreeMap<Package, String>> {
+        let source_url = self.source.as_url().to_string();
+        let mut pkg_ids = Vec::new();
+        for package in graph.resolved_packages() {
+            // Local registries have no URL form Cargo can parse back.
+            let source = if package.source == source_url {
+                self.source
+            } else {
+                SourceId::from_url(&package.source)?
+            };
+            let Package { name, version } = &package.package;
+            pkg_ids.push(PackageId::try_new(name, version, source)?);
+        }
+
+        let _lock = self
+            .config
+            .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        let packages = download(self.config, &pkg_ids)?;
+        let licenses = packages
+            .get_many(pkg_ids.iter().copied())?
+            .into_iter()
+            .filter_map(|package| {
+                let license = package.manifest().metadata().license.clone()?;
+                Some((Package::from(package.package_id()), license))
+            })
+            .collect();
+        Ok(licenses)
+    }
+
+    /// Explain why the crate named `target` is a dependency of a package.
+    ///
+    /// Returns every path from the package to a version of `target` through
+    /// its dependency graph, as the edges along the path. Each edge has the
+    /// version requirement and activating features of its hop.
+    pub fn why(
+        &mut self,
+        package: &str,
+        version: Option<&str>,
+        target: &str,
+    ) -> Result<(Vec<Vec<Edge>>, Vec<UnresolvedFeature>)> {
+        let (graph, unresolved_features) = self.dependency_graph(package, version)?;
+        let paths = match graph.roots().next() {
+            Some(root) => graph
+                .paths(root, target)
+                .into_iter()
+                .map(|path| path.into_iter().cloned().collect())
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok((paths, unresolved_features))
+    }
+
+    /// Compare the dependency graph of the `old` request with that of the
+    /// `new` one, e.g. two versions of a crate. Returns the differences along
+    /// with the features of either request whose dependencies couldn't be
+    /// resolved.
+    pub fn diff(
+        &mut self,
+        old: &ResolveRequest,
+        new: &ResolveRequest,
+    ) -> Result<(GraphDiff, Vec<UnresolvedFeature>)> {
+        let (old_graph, mut unresolved_features) = self.resolve(old)?;
+        let (new_graph, new_unresolved_features) = self.resolve(new)?;
+        unresolved_features.extend(new_unresolved_features);
+        Ok((GraphDiff::new(&old_graph, &new_graph), unresolved_features))
+    }
+
+    /// Get the dependencies for a single package, attributed to the features
+    /// of the package that add them.
+    pub fn feature_dependencies(
+        &mut self,
+        package: &str,
+        version: Option<&str>,
+    ) -> Result<(FeatureDependencies, Vec<UnresolvedFeature>)> {
+        let mut dep = Dependency::parse(package, version, self.source)?;
+        dep.set_default_features(false);
 
-        // Get a full summary for the package in question so we can enumerate its
-        // features.
         let _lock = self
             .config
             .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
-        let summary = get_package_summary(&mut *self.registry, &dep)?;
+        let summary = get_package_summary(&mut *self.registry, &self.versions, &dep)?;
+        let mut query = Query::new(dep);
+        query.dep.lock_version(summary.version());
+        query.preferred = self
+            .versions
+            .direct_minimal(&mut *self.registry, &summary, false)?;
 
-        // First get a list of all dependencies required if no features are enabled.
+        // Resolve once with every feature disabled (including the default
+        // features) to get a baseline, then once for each feature on its own.
+        let mut graph = DependencyGraph::new();
         query_dependencies(
-            &self.config,
+            self.config,
             self.source,
             &mut *self.registry,
-            &dep,
-            dependencies,
+            &self.versions,
+            &mut self.platforms,
+            &query,
+            &mut graph,
         )?;
+        let mut dependencies = FeatureDependencies {
+            baseline: graph.packages().cloned().collect(),
+            features: BTreeMap::new(),
+        };
 
-        // Try to incrementally enable every feature that may activate an optional
-        // dependency, and merge the dependency requirements with our original
-        // list. We don't toggle all features at once in case a package declares
-        // conflicting features.
         let mut unresolved_features = Vec::new();
-        for (feature, fv) in summary.features() {
-            if fv.iter().any(|fv| {
-                matches!(
-                    fv,
-                    FeatureValue::Dep { .. } | FeatureValue::DepFeature { .. }
-                )
-            }) {
-                let mut dep = dep.clone();
-                dep.set_features([*feature]);
-                if let Err(error) = query_dependencies(
-                    &self.config,
-                    self.source,
-                    &mut *self.registry,
-                    &dep,
-                    dependencies,
-                ) {
-                    unresolved_features.push(UnresolvedFeature {
-                        name: feature.as_str().to_string(),
-                        error,
-                    });
+        for feature in summary.features().keys() {
+            let mut query = query.clone();
+            query.dep.set_features([*feature]);
+            let mut graph = DependencyGraph::new();
+            match query_dependencies(
+                self.config,
+                self.source,
+                &mut *self.registry,
+                &self.versions,
+                &mut self.platforms,
+                &query,
+                &mut graph,
+            ) {
+                Ok(()) => {
+                    let added = graph
+                        .packages()
+                        .filter(|p| !dependencies.baseline.contains(p))
+                        .cloned()
+                        .collect();
+                    dependencies.features.insert(feature.to_string(), added);
                 }
+                Err(error) => unresolved_features.push(UnresolvedFeature {
+                    name: feature.as_str().to_string(),
+                    error,
+                }),
             }
         }
 
-        dependencies.remove(&Package {
-            name: DUMMY_PACKAGE_NAME.to_string(),
-            version: DUMMY_PACKAGE_VERSION.to_string(),
-        });
+        Ok((dependencies, unresolved_features))
+    }
+}
 
-        Ok(unresolved_features)
+/// Download packages to Cargo's package cache, or unpack them from a local
+/// registry. The package cache must be locked while getting them.
+pub(crate) fn download<'cfg>(
+    config: &'cfg Config,
+    pkg_ids: &[PackageId],
+) -> Result<PackageSet<'cfg>> {
+    // Getting the packages consumes the registry, so it can't be shared.
+    let mut registry = PackageRegistry::new(config)?;
+    registry.add_sources(pkg_ids.iter().map(|id| id.source_id()))?;
+    Ok(registry.get(pkg_ids)?)
+}
+
+/// Get the IDs a locked package may have: its ID in the source it was locked
+/// to and, if that's a registry, its ID in the resolver's `registry`.
+fn locked_package_ids(
+    package: &Package,
+    source: &str,
+    registry: SourceId,
+) -> Result<Vec<PackageId>> {
+    let name = InternedString::new(&package.name);
+    let version = package.version.parse::<semver::Version>().map_err(|e| {
+        Error::InvalidLockfile(format!("{} {}: {e}", package.name, package.version))
+    })?;
+    let mut pkg_ids = Vec::new();
+    if let Ok(source) = SourceId::from_url(source) {
+        pkg_ids.push(PackageId::new(name, version.clone(), source));
     }
+    if ["registry+", "sparse+", "local-registry+"]
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+    {
+        pkg_ids.push(PackageId::new(name, version, registry));
+    }
+    Ok(pkg_ids)
 }
 
-impl Drop for Resolver {
-    fn drop(&mut self) {
-        // The `registry` field holds a reference to `config`. Ensure
-        // `registry` is dropped first.
-        unsafe {
-            ManuallyDrop::drop(&mut self.registry);
-            ManuallyDrop::drop(&mut self.config);
+/// How the versions of packages are chosen when resolving.
+struct Versions {
+    strategy: VersionStrategy,
+    /// The locked packages, which are preferred to other versions.
+    locked: Vec<PackageId>,
+}
+
+impl Versions {
+    /// Get the version preferences for a resolve, preferring the `extra`
+    /// packages as well as the locked ones.
+    fn preferences(&self, extra: &[PackageId]) -> VersionPreferences {
+        let mut version_prefs = VersionPreferences::default();
+        if self.strategy == VersionStrategy::Minimal {
+            version_prefs.version_ordering(VersionOrdering::MinimumVersionsFirst);
+        }
+        for &pkg_id in self.locked.iter().chain(extra) {
+            version_prefs.prefer_package_id(pkg_id);
         }
+        version_prefs
+    }
+
+    /// Get the oldest versions of the direct dependencies of the package
+    /// `summary`, if they should be preferred.
+    fn direct_minimal(
+        &self,
+        registry: &mut dyn Registry,
+        summary: &Summary,
+        dev_deps: bool,
+    ) -> Result<Vec<PackageId>> {
+        if self.strategy != VersionStrategy::DirectMinimal {
+            return Ok(Vec::new());
+        }
+        let mut pkg_ids = Vec::new();
+        for dep in summary.dependencies() {
+            if dep.kind() == CargoDepKind::Development && !dev_deps {
+                continue;
+            }
+            let minimal = query_summaries(registry, dep)?
+                .iter()
+                .map(Summary::package_id)
+                .min_by(|a, b| a.version().cmp(b.version()));
+            pkg_ids.extend(minimal);
+        }
+        Ok(pkg_ids)
     }
 }
 
-fn get_package_summary<R: Registry>(registry: &mut R, dep: &Dependency) -> Result<Summary> {
+fn query_summaries(registry: &mut dyn Registry, dep: &Dependency) -> Result<Vec<Summary>> {
     let mut summaries = Vec::new();
     loop {
         if registry
@@ -181,32 +1018,165 @@ fn get_package_summary<R: Registry>(registry: &mut R, dep: &Dependency) -> Resul
         }
         registry.block_until_ready()?;
     }
+    Ok(summaries)
+}
 
+/// Get the summary of the newest (or locked) version of the package that
+/// `dep` matches.
+fn get_package_summary(
+    registry: &mut dyn Registry,
+    versions: &Versions,
+    dep: &Dependency,
+) -> Result<Summary> {
+    let mut summaries = query_summaries(registry, dep)?;
     if summaries.is_empty() {
-        return Err(Error::PackageNotFound {
-            name: dep.package_name().to_string(),
-            version: match dep.version_req() {
-                OptVersionReq::Any => None,
-                OptVersionReq::Req(vr) => Some(vr.to_string()),
-                OptVersionReq::Locked(v, _) => Some(v.to_string()),
-                OptVersionReq::UpdatePrecise(v, _) => Some(v.to_string()),
-            },
-        });
-    }
-
-    VersionPreferences::default()
+        return Err(package_not_found(dep));
+    }
+
+    versions
+        .preferences(&[])
         .sort_summaries(&mut summaries, Some(VersionOrdering::MaximumVersionsFirst));
 
     Ok(summaries.into_iter().next().unwrap())
 }
 
-fn query_dependencies<R: Registry>(
+fn package_not_found(dep: &Dependency) -> Error {
+    Error::PackageNotFound {
+        name: dep.package_name().to_string(),
+        version: match dep.version_req() {
+            OptVersionReq::Any => None,
+            OptVersionReq::Req(vr) => Some(vr.to_string()),
+            OptVersionReq::Locked(v, _) => Some(v.to_string()),
+            OptVersionReq::UpdatePrecise(v, _) => Some(v.to_string()),
+        },
+    }
+}
+
+/// Create the query for a request, and get the summary of the requested
+/// package so its features can be enumerated. The features to enable are left
+/// to the caller.
+fn request_query(
+    registry: &mut dyn Registry,
+    versions: &Versions,
+    source: SourceId,
+    request: &ResolveRequest,
+) -> Result<(Query, Summary)> {
+    let mut dep = Dependency::parse(&request.package, request.version.as_deref(), source)?;
+    dep.set_default_features(request.default_features);
+    let summary = get_package_summary(registry, versions, &dep)?;
+
+    // Pin the package to the version whose features and dev-dependencies are
+    // used, whatever the version strategy.
+    dep.lock_version(summary.version());
+    let mut query = Query {
+        dep,
+        dev_deps: Vec::new(),
+        build_deps: request.build_dependencies,
+        preferred: versions.direct_minimal(registry, &summary, request.dev_dependencies)?,
+    };
+    if request.dev_dependencies {
+        query.dev_deps = summary
+            .dependencies()
+            .iter()
+            .filter(|d| d.kind() == CargoDepKind::Development)
+            .cloned()
+            .collect();
+    }
+    Ok((query, summary))
+}
+
+/// Get the dependency graph for a request, merging it into `graph`. The
+/// package cache must be locked.
+fn request_dependencies(
     config: &Config,
     source: SourceId,
-    registry: &mut R,
-    dep: &Dependency,
-    all_deps: &mut HashSet<Package>,
-) -> Result<()> {
+    registry: &mut dyn Registry,
+    versions: &Versions,
+    platforms: &mut Platforms,
+    request: &ResolveRequest,
+    graph: &mut DependencyGraph,
+) -> Result<Vec<UnresolvedFeature>> {
+    let (mut query, summary) = request_query(registry, versions, source, request)?;
+
+    let features = match &request.features {
+        FeatureSelection::EachFeature => None,
+        FeatureSelection::Features(features) => {
+            Some(features.iter().map(|f| InternedString::new(f)).collect())
+        }
+        FeatureSelection::AllFeatures => {
+            Some(summary.features().keys().copied().collect::<Vec<_>>())
+        }
+    };
+    if let Some(features) = features {
+        query.dep.set_features(features);
+        query_dependencies(config, source, registry, versions, platforms, &query, graph)?;
+        return Ok(Vec::new());
+    }
+
+    // First get a list of all dependencies required if no features are enabled.
+    query_dependencies(config, source, registry, versions, platforms, &query, graph)?;
+
+    // Try to incrementally enable every feature that may activate an optional
+    // dependency, and merge the dependency requirements with our original
+    // list. We don't toggle all features at once in case a package declares
+    // conflicting features.
+    let mut unresolved_features = Vec::new();
+    for (feature, fv) in summary.features() {
+        if fv.iter().any(|fv| {
+            matches!(
+                fv,
+                FeatureValue::Dep { .. } | FeatureValue::DepFeature { .. }
+            )
+        }) {
+            let mut query = query.clone();
+            query.dep.set_features([*feature]);
+            if let Err(error) =
+                query_dependencies(config, source, registry, versions, platforms, &query, graph)
+            {
+                unresolved_features.push(UnresolvedFeature {
+                    name: feature.as_str().to_string(),
+                    error,
+                });
+            }
+        }
+    }
+
+    Ok(unresolved_features)
+}
+
+/// The dependencies of the dummy package used to resolve a single package.
+#[derive(Clone)]
+struct Query {
+    /// The dependency on the package being resolved.
+    dep: Dependency,
+    /// The package's dev-dependencies to include.
+    dev_deps: Vec<Dependency>,
+    /// Whether to include build dependencies.
+    build_deps: bool,
+    /// Packages to prefer in addition to the locked ones.
+    preferred: Vec<PackageId>,
+}
+
+impl Query {
+    fn new(dep: Dependency) -> Self {
+        Self {
+            dep,
+            dev_deps: Vec::new(),
+            build_deps: true,
+            preferred: Vec::new(),
+        }
+    }
+}
+
+/// Resolve a query, returning the resolve and the ID of the dummy package
+/// whose dependencies were resolved.
+fn resolve_query(
+    config: &Config,
+    source: SourceId,
+    registry: &mut dyn Registry,
+    versions: &Versions,
+    query: &Query,
+) -> Result<(Resolve, PackageId)> {
     let pkg_id = PackageId::new(
         InternedString::new(DUMMY_PACKAGE_NAME),
         DUMMY_PACKAGE_VERSION,
@@ -214,7 +1184,10 @@ fn query_dependencies<R: Registry>(
     );
     let summary = Summary::new(
         pkg_id,
-        vec![dep.clone()],
+        std::iter::once(&query.dep)
+            .chain(&query.dev_deps)
+            .cloned()
+            .collect(),
         &BTreeMap::new(),
         None::<InternedString>,
         None,
@@ -223,31 +1196,1480 @@ fn query_dependencies<R: Registry>(
         true,
         RequestedFeatures::CliFeatures(CliFeatures::new_all(true)),
     );
-    let version_prefs = VersionPreferences::default();
-
     let result = resolver::resolve(
         &[(summary, resolve_opts)],
         &[],
         registry,
-        &version_prefs,
+        &versions.preferences(&query.preferred),
         Some(config),
     )?;
+    Ok((result, pkg_id))
+}
 
-    all_deps.extend(result.iter().map(|p| Package {
-        name: p.name().to_string(),
-        version: p.version().to_string(),
-    }));
+fn query_dependencies(
+    config: &Config,
+    source: SourceId,
+    registry: &mut dyn Registry,
+    versions: &Versions,
+    platforms: &mut Platforms,
+    query: &Query,
+    graph: &mut DependencyGraph,
+) -> Result<()> {
+    let (result, pkg_id) = resolve_query(config, source, registry, versions, query)?;
+    platforms.load_proc_macros(config, result.iter().filter(|&id| id != pkg_id))?;
 
+    // The package's dev-dependencies are dependencies of the dummy package, so
+    // reattach them to the root.
+    let mut roots = Vec::new();
+    let mut dev_deps = Vec::new();
+    for (to, deps) in result.deps(pkg_id) {
+        for dep in deps {
+            if dep.kind() == CargoDepKind::Development {
+                dev_deps.push((to, dep));
+            } else {
+                roots.push(to);
+            }
+        }
+    }
+    add_resolve(
+        &result,
+        &roots,
+        &dev_deps,
+        platforms,
+        query.build_deps,
+        graph,
+    );
     Ok(())
 }
 
+/// Walk a resolve from the `roots`, adding the packages and edges reached to
+/// `graph`. Dependencies that don't apply to the `platforms` are skipped.
+/// `dev_deps` are extra edges from the first root.
+fn add_resolve(
+    result: &Resolve,
+    roots: &[PackageId],
+    dev_deps: &[(PackageId, &Dependency)],
+    platforms: &Platforms,
+    build_deps: bool,
+    graph: &mut DependencyGraph,
+) {
+    for &root in roots {
+        graph.add_root(ResolvedPackage::new(result, root));
+    }
+    // Packages are visited once for the targets and once for the host, since
+    // their dependencies may apply to only one of them.
+    let mut queue = roots
+        .iter()
+        .map(|&root| (root, platforms.is_proc_macro(root)))
+        .collect::<Vec<_>>();
+    let mut visited = queue.iter().copied().collect::<HashSet<_>>();
+    let edges = |from| {
+        let dev_deps = if Some(&from) == roots.first() {
+            dev_deps
+        } else {
+            &[]
+        };
+        result
+            .deps(from)
+            .flat_map(|(to, deps)| deps.iter().map(move |dep| (to, dep)))
+            .chain(dev_deps.iter().copied())
+            .collect::<Vec<_>>()
+    };
+    while let Some((from, on_host)) = queue.pop() {
+        for (to, dep) in edges(from) {
+            if !build_deps && dep.is_build() {
+                continue;
+            }
+            let for_host = on_host || dep.is_build();
+            if !platforms.matches(dep, for_host) {
+                continue;
+            }
+            graph.add_edge(Edge {
+                from: Package::from(from),
+                to: Package::from(to),
+                version_req: dep.version_req().to_string(),
+                kind: dep.kind().into(),
+                optional: dep.is_optional(),
+                features: activating_features(result, from, dep),
+                target: dep.platform().map(|p| p.to_string()),
+            });
+            let to_host = for_host || platforms.is_proc_macro(to);
+            if visited.insert((to, to_host)) {
+                graph.add_package(ResolvedPackage::new(result, to));
+                queue.push((to, to_host));
+            }
+        }
+    }
+}
+
+/// Get the enabled features of `pkg_id` that directly activate the
+/// dependency `dep`, if it's optional, or enable one of its features (e.g.
+/// `dep/feature`).
+fn activating_features(resolve: &Resolve, pkg_id: PackageId, dep: &Dependency) -> BTreeSet<String> {
+    let feature_map = resolve.summary(pkg_id).features();
+    resolve
+        .features(pkg_id)
+        .iter()
+        .filter(|feature| {
+            feature_map.get(*feature).is_some_and(|values| {
+                values.iter().any(|fv| match fv {
+                    FeatureValue::Dep { dep_name } => *dep_name == dep.name_in_toml(),
+                    // A weak `dep?/feature` also applies, since the dependency
+                    // is active if there's an edge to it.
+                    FeatureValue::DepFeature { dep_name, .. } => *dep_name == dep.name_in_toml(),
+                    FeatureValue::Feature(_) => false,
+                })
+            })
+        })
+        .map(|feature| feature.to_string())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{
+        package, resolved, write_package, MockRegistry, TestDep, TestPackage, TestRegistry,
+        EXAMPLE_SOURCE,
+    };
+
+    #[test]
+    fn mock_registry() {
+        let mut registry = MockRegistry::new();
+        for package in [
+            TestPackage::new("leaf", "1.0.0"),
+            TestPackage::new("leaf", "1.1.0"),
+            TestPackage::new("extra", "0.3.0"),
+            TestPackage::new("cc", "1.0.0"),
+            TestPackage::new("tester", "0.1.0"),
+            TestPackage::new("root", "0.1.0")
+                .dep(TestDep::new("leaf", "^1"))
+                .dep(TestDep::new("extra", "^0.3").optional())
+                .dep(TestDep::new("missing", "^1").optional())
+                .dep(TestDep::new("cc", "^1").build())
+                .dep(TestDep::new("tester", "^0.1").dev())
+                .feature("more", &["dep:extra"])
+                .feature("broken", &["dep:missing"]),
+        ] {
+            registry.publish(&package);
+        }
+        let cargo_home = tempfile::tempdir().unwrap();
+        let config = MockRegistry::config(cargo_home.path());
+        let mut resolver = registry.resolver(Resolver::builder(), &config);
+
+        let (graph, errs) = resolver
+            .resolve(&ResolveRequest::new("root", None))
+            .unwrap();
+        let names = errs.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["broken"]);
+        assert_eq!(
+            graph.packages().cloned().collect::<HashSet<_>>(),
+            HashSet::from([
+                package("root", "0.1.0"),
+                package("leaf", "1.1.0"),
+                package("extra", "0.3.0"),
+                package("cc", "1.0.0"),
+            ])
+        );
+        let root = package("root", "0.1.0");
+        let extra = graph
+            .dependencies(&root)
+            .find(|e| e.to.name == "extra")
+            .unwrap();
+        assert_eq!(extra.features, BTreeSet::from(["more".to_string()]));
+
+        let request = ResolveRequest::new("root", None)
+            .features(["more"])
+            .no_build_dependencies()
+            .dev_dependencies();
+        let (graph, errs) = resolver.resolve(&request).unwrap();
+        assert!(errs.is_empty());
+        assert_eq!(
+            graph.packages().cloned().collect::<HashSet<_>>(),
+            HashSet::from([
+                package("root", "0.1.0"),
+                package("leaf", "1.1.0"),
+                package("extra", "0.3.0"),
+                package("tester", "0.1.0"),
+            ])
+        );
+
+        let versions = resolver
+            .resolve_versions(&ResolveRequest::new("leaf", None))
+            .unwrap();
+        assert_eq!(
+            versions.keys().map(|v| v.to_string()).collect::<Vec<_>>(),
+            ["1.0.0", "1.1.0"]
+        );
+    }
+
+    #[test]
+    fn mock_versions() {
+        let mut registry = MockRegistry::new();
+        for version in ["1.0.0", "1.1.0"] {
+            registry.publish(&TestPackage::new("deep", version));
+            registry.publish(
+                &TestPackage::new("mid", version).dep(TestDep::new("deep", &format!("^{version}"))),
+            );
+        }
+        registry.publish(&TestPackage::new("root", "0.1.0").dep(TestDep::new("mid", "^1")));
+        let cargo_home = tempfile::tempdir().unwrap();
+        let config = MockRegistry::config(cargo_home.path());
+
+        let packages = |builder: ResolverBuilder| {
+            let mut resolver = registry.clone().resolver(builder, &config);
+            let request = ResolveRequest::new("root", None);
+            let (graph, _) = resolver.resolve(&request).unwrap();
+            graph
+                .packages()
+                .map(|p| format!("{} {}", p.name, p.version))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            packages(Resolver::builder()),
+            ["deep 1.1.0", "mid 1.1.0", "root 0.1.0"]
+        );
+        assert_eq!(
+            packages(Resolver::builder().version_strategy(VersionStrategy::Minimal)),
+            ["deep 1.0.0", "mid 1.0.0", "root 0.1.0"]
+        );
+        assert_eq!(
+            packages(Resolver::builder().version_strategy(VersionStrategy::DirectMinimal)),
+            ["deep 1.1.0", "mid 1.0.0", "root 0.1.0"]
+        );
+
+        let mut previous = DependencyGraph::new();
+        previous.add_package(resolved(&package("mid", "1.0.0"), EXAMPLE_SOURCE, &[]));
+        assert_eq!(
+            packages(Resolver::builder().previous(&previous)),
+            ["deep 1.1.0", "mid 1.0.0", "root 0.1.0"]
+        );
+    }
+
+    #[test]
+    fn local_registry() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("leaf", "1.1.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("extra", "^0.3").optional())
+            .feature("more", &["dep:extra"])
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (deps, errs) = resolver.dependencies("root", None).unwrap();
+        assert!(errs.is_empty());
+        assert_eq!(
+            deps,
+            HashSet::from([
+                package("root", "0.1.0"),
+                package("leaf", "1.1.0"),
+                package("extra", "0.3.0"),
+            ])
+        );
+    }
+
+    #[test]
+    fn dependency_graph_edges() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.1.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0")
+            .dep(TestDep::new("leaf", "^1.1"))
+            .publish(&registry);
+        TestPackage::new("gen", "2.0.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("extra", "^0.3").optional())
+            .dep(TestDep::new("gen", "^2").build())
+            .feature("more", &["dep:extra"])
+            .feature("all", &["more"])
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (graph, errs) = resolver.dependency_graph("root", None).unwrap();
+        assert!(errs.is_empty());
+
+        let root = package("root", "0.1.0");
+        assert_eq!(graph.roots().collect::<Vec<_>>(), [&root]);
+        assert_eq!(graph.packages().count(), 4);
+
+        let edges = graph
+            .dependencies(&root)
+            .map(|e| (e.to.name.as_str(), e))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(edges.len(), 3);
+        assert_eq!(edges["leaf"].version_req, "^1");
+        assert_eq!(edges["leaf"].kind, DepKind::Normal);
+        assert!(!edges["leaf"].optional);
+        assert!(edges["leaf"].features.is_empty());
+        assert_eq!(edges["gen"].kind, DepKind::Build);
+        assert!(edges["extra"].optional);
+        assert_eq!(
+            edges["extra"].features,
+            BTreeSet::from(["more".to_string()])
+        );
+
+        let leaf = package("leaf", "1.1.0");
+        let mut dependents = graph
+            .dependents(&leaf)
+            .map(|e| e.from.name.as_str())
+            .collect::<Vec<_>>();
+        dependents.sort();
+        assert_eq!(dependents, ["extra", "root"]);
+    }
+
+    #[test]
+    fn workspace() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("leaf", "1.1.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .publish(&registry);
+        TestPackage::new("tester", "0.1.0").publish(&registry);
+        let cargo_home = tempfile::tempdir().unwrap();
+        registry.replace_crates_io(cargo_home.path());
+
+        let ws = tempfile::tempdir().unwrap();
+        std::fs::write(
+            ws.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\", \"core\"]\nresolver = \"2\"\n\n\
+             [patch.crates-io]\nextra = { path = \"extra\" }\n",
+        )
+        .unwrap();
+        write_package(
+            &ws.path().join("app"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\ncore = { path = \"../core\" }\n\
+             extra = { version = \"0.3\", optional = true }\n\n\
+             [dev-dependencies]\ntester = \"0.1\"\n",
+        );
+        write_package(
+            &ws.path().join("core"),
+            "[package]\nname = \"core\"\nversion = \"0.2.0\"\n\n\
+             [dependencies]\nleaf = \"1\"\n",
+        );
+        std::fs::write(
+            ws.path().join("Cargo.lock"),
+            format!(
+                "version = 3\n\n\
+                 [[package]]\nname = \"core\"\nversion = \"0.2.0\"\n\
+                 dependencies = [\"leaf\"]\n\n\
+                 [[package]]\nname = \"leaf\"\nversion = \"1.0.0\"\n\
+                 source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\
+                 checksum = \"{}\"\n",
+                TestPackage::new("leaf", "1.0.0").checksum()
+            ),
+        )
+        .unwrap();
+        write_package(
+            &ws.path().join("extra"),
+            "[package]\nname = \"extra\"\nversion = \"0.3.1\"\n",
+        );
+
+        let builder = Resolver::builder()
+            .cwd(ws.path())
+            .cargo_home(cargo_home.path());
+        let config = builder.config().unwrap();
+        let mut resolver = builder.build(&config).unwrap();
+        let graph = resolver
+            .resolve_workspace(&WorkspaceRequest::new(ws.path()))
+            .unwrap();
+        assert_eq!(
+            graph.roots().collect::<Vec<_>>(),
+            [&package("app", "0.1.0"), &package("core", "0.2.0")]
+        );
+        assert_eq!(
+            graph.packages().collect::<Vec<_>>(),
+            [
+                &package("app", "0.1.0"),
+                &package("core", "0.2.0"),
+                &package("leaf", "1.0.0"),
+            ]
+        );
+        let leaf = graph.package(&package("leaf", "1.0.0")).unwrap();
+        assert_eq!(
+            leaf.source,
+            SourceId::crates_io(resolver.config)
+                .unwrap()
+                .as_url()
+                .to_string()
+        );
+
+        // Without the lockfile, the newest version of `leaf` is used.
+        let graph = resolver
+            .resolve_workspace(&WorkspaceRequest::new(ws.path()).no_lockfile())
+            .unwrap();
+        assert!(graph.contains(&package("leaf", "1.1.0")));
+        assert!(!graph.contains(&package("leaf", "1.0.0")));
+
+        // The patch replaces `extra` from the registry, and has no dependencies.
+        let request = WorkspaceRequest::new(ws.path().join("app/Cargo.toml"))
+            .features(["app/extra"])
+            .dev_dependencies();
+        let graph = resolver.resolve_workspace(&request).unwrap();
+        let app = package("app", "0.1.0");
+        let mut deps = graph
+            .dependencies(&app)
+            .map(|e| (e.to.name.as_str(), e.to.version.as_str(), e.kind))
+            .collect::<Vec<_>>();
+        deps.sort();
+        assert_eq!(
+            deps,
+            [
+                ("core", "0.2.0", DepKind::Normal),
+                ("extra", "0.3.1", DepKind::Normal),
+                ("tester", "0.1.0", DepKind::Development),
+            ]
+        );
+        assert!(graph
+            .package(&package("extra", "0.3.1"))
+            .unwrap()
+            .source
+            .starts_with("path+"));
+
+        assert!(resolver
+            .resolve_workspace(&WorkspaceRequest::new(ws.path().join("missing")))
+            .is_err());
+    }
+
+    #[test]
+    fn lockfile() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("leaf", "2.0.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .publish(&registry);
+        TestPackage::new("tester", "0.1.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^2"))
+            .dep(TestDep::new("extra", "^0.3").optional())
+            .dep(TestDep::new("tester", "^0.1").dev())
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let request = ResolveRequest::new("root", None);
+        let lockfile = resolver
+            .lockfile(&request, LockfileVersion::default())
+            .unwrap();
+        assert!(lockfile.starts_with(
+            "# This file is automatically @generated by Cargo.\n\
+             # It is not intended for manual editing.\n\
+             version = 3\n\n[[package]]\nname = \"extra\"\n"
+        ));
+        assert!(!lockfile.contains(DUMMY_PACKAGE_NAME));
+        assert!(lockfile.contains(
+            "name = \"root\"\n\
+             version = \"0.1.0\"\n\
+             dependencies = [\n \"extra\",\n \"leaf 2.0.0\",\n \"tester\",\n]\n",
+        ));
+        assert!(lockfile.ends_with("\"\n"));
+        toml::from_str::<resolver::EncodableResolve>(&lockfile).unwrap();
+        assert_eq!(lockfile.matches("[[package]]").count(), 5);
+
+        // Every feature and dev-dependency is locked, whatever the request.
+        let request = ResolveRequest::new("root", None)
+            .no_default_features()
+            .features(Vec::<String>::new())
+            .no_build_dependencies();
+        let v4 = resolver.lockfile(&request, LockfileVersion::V4).unwrap();
+        assert_eq!(v4, lockfile.replace("version = 3\n", "version = 4\n"));
+    }
+
+    #[test]
+    fn lockfile_builds_root() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("leaf", "2.0.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .publish(&registry);
+        TestPackage::new("speedy", "1.0.0").publish(&registry);
+        TestPackage::new("tester", "0.1.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^2"))
+            .dep(TestDep::new("extra", "^0.3").optional())
+            .dep(TestDep::new("speedy", "^1").optional())
+            .dep(TestDep::new("tester", "^0.1").dev())
+            .feature("fast", &["dep:speedy"])
+            .publish(&registry);
+        let cargo_home = tempfile::tempdir().unwrap();
+        registry.replace_crates_io(cargo_home.path());
+        let dir = tempfile::tempdir().unwrap();
+        write_package(
+            dir.path(),
+            "[package]\nname = \"root\"\nversion = \"0.1.0\"\n\n\
+             [dependencies]\nleaf = \"2\"\nextra = { version = \"0.3\", optional = true }\n\
+             speedy = { version = \"1\", optional = true }\n\n\
+             [dev-dependencies]\ntester = \"0.1\"\n\n\
+             [features]\nfast = [\"dep:speedy\"]\n",
+        );
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        for request in [
+            ResolveRequest::new("root", None),
+            ResolveRequest::new("root", None)
+                .features(Vec::<String>::new())
+                .dev_dependencies(),
+            ResolveRequest::new("root", None).all_features(),
+        ] {
+            let lockfile = resolver
+                .lockfile(&request, LockfileVersion::default())
+                .unwrap();
+
+            // Cargo must accept the lockfile as is for the root's source,
+            // like `cargo metadata --locked`.
+            fs::write(dir.path().join("Cargo.lock"), &lockfile).unwrap();
+            let mut config = Config::new(
+                Shell::new(),
+                dir.path().to_path_buf(),
+                cargo_home.path().to_path_buf(),
+            );
+            config
+                .configure(0, true, None, false, true, false, &None, &[], &[])
+                .unwrap();
+            let ws = Workspace::new(&dir.path().join("Cargo.toml"), &config).unwrap();
+            ops::resolve_ws(&ws).unwrap();
+            assert_eq!(
+                fs::read_to_string(dir.path().join("Cargo.lock")).unwrap(),
+                lockfile
+            );
+        }
+    }
+
+    #[test]
+    fn locked_versions() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("leaf", "1.1.0").publish(&registry);
+        TestPackage::new("leaf", "2.0.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .publish(&registry);
+        TestPackage::new("root", "0.2.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .publish(&registry);
+
+        // Pins that match, a pin that doesn't satisfy `^1`, and a path package.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Pinned.lock");
+        std::fs::write(
+            &path,
+            "version = 3\n\n\
+             [[package]]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [[package]]\nname = \"leaf\"\nversion = \"1.0.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"leaf\"\nversion = \"2.0.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"root\"\nversion = \"0.1.0\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        )
+        .unwrap();
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (graph, _) = resolver.dependency_graph("root", None).unwrap();
+        assert_eq!(
+            graph.packages().collect::<Vec<_>>(),
+            [&package("leaf", "1.1.0"), &package("root", "0.2.0")]
+        );
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::builder()
+            .local_registry(registry.path())
+            .lockfile(&path)
+            .build(&config)
+            .unwrap();
+        let (locked, _) = resolver.dependency_graph("root", None).unwrap();
+        assert_eq!(
+            locked.packages().collect::<Vec<_>>(),
+            [&package("leaf", "1.0.0"), &package("root", "0.1.0")]
+        );
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::builder()
+            .local_registry(registry.path())
+            .previous(&locked)
+            .build(&config)
+            .unwrap();
+        let (graph, _) = resolver.dependency_graph("root", None).unwrap();
+        assert_eq!(graph, locked);
+
+        let builder = Resolver::builder()
+            .local_registry(registry.path())
+            .cwd(dir.path())
+            .lockfile("Pinned.lock");
+        let cwd_config = builder.config().unwrap();
+        let mut resolver = builder.build(&cwd_config).unwrap();
+        let (graph, _) = resolver.dependency_graph("root", None).unwrap();
+        assert_eq!(graph, locked);
+
+        std::fs::write(&path, "[[package]]\nname = \"leaf\"\n").unwrap();
+        let result = Resolver::builder()
+            .local_registry(registry.path())
+            .lockfile(&path)
+            .build(&config);
+        assert!(matches!(result, Err(Error::InvalidLockfile(_))));
+    }
+
+    #[test]
+    fn version_strategies() {
+        let registry = TestRegistry::new();
+        for version in ["1.0.0", "1.1.0"] {
+            TestPackage::new("deep", version).publish(&registry);
+            TestPackage::new("mid", version)
+                .dep(TestDep::new("deep", &format!("^{version}")))
+                .publish(&registry);
+        }
+        for version in ["0.1.0", "0.2.0"] {
+            TestPackage::new("root", version)
+                .dep(TestDep::new("mid", "^1"))
+                .publish(&registry);
+        }
+
+        let packages = |strategy| {
+            let config = Config::default().unwrap();
+            let mut resolver = Resolver::builder()
+                .local_registry(registry.path())
+                .version_strategy(strategy)
+                .build(&config)
+                .unwrap();
+            let (graph, _) = resolver.dependency_graph("root", None).unwrap();
+            graph
+                .packages()
+                .map(|p| format!("{} {}", p.name, p.version))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            packages(VersionStrategy::Maximum),
+            ["deep 1.1.0", "mid 1.1.0", "root 0.2.0"]
+        );
+        assert_eq!(
+            packages(VersionStrategy::Minimal),
+            ["deep 1.0.0", "mid 1.0.0", "root 0.2.0"]
+        );
+        assert_eq!(
+            packages(VersionStrategy::DirectMinimal),
+            ["deep 1.1.0", "mid 1.0.0", "root 0.2.0"]
+        );
+    }
+
+    #[test]
+    fn resolve_versions() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0").publish(&registry);
+        TestPackage::new("root", "0.9.0").publish(&registry);
+        TestPackage::new("root", "1.0.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .publish(&registry);
+        TestPackage::new("root", "1.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("extra", "^0.3"))
+            .publish(&registry);
+        TestPackage::new("root", "1.2.0")
+            .dep(TestDep::new("missing", "^1"))
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let versions = resolver
+            .resolve_versions(&ResolveRequest::new("root", Some("1")))
+            .unwrap();
+        assert_eq!(
+            versions.keys().map(ToString::to_string).collect::<Vec<_>>(),
+            ["1.0.0", "1.1.0", "1.2.0"]
+        );
+        let packages = |version: &str| {
+            let (graph, errs) = versions[&version.parse().unwrap()].as_ref().unwrap();
+            assert!(errs.is_empty());
+            graph
+                .packages()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(packages("1.0.0"), ["leaf", "root"]);
+        assert_eq!(packages("1.1.0"), ["extra", "leaf", "root"]);
+        assert!(versions[&"1.2.0".parse().unwrap()].is_err());
+
+        assert!(matches!(
+            resolver.resolve_versions(&ResolveRequest::new("root", Some("2"))),
+            Err(Error::PackageNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn diff() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("leaf", "2.0.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .publish(&registry);
+        TestPackage::new("root", "0.2.0")
+            .dep(TestDep::new("leaf", "^2"))
+            .dep(TestDep::new("extra", "^0.3").optional())
+            .feature("more", &["dep:extra"])
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (diff, errs) = resolver
+            .diff(
+                &ResolveRequest::new("root", Some("=0.1.0")),
+                &ResolveRequest::new("root", Some("=0.2.0")),
+            )
+            .unwrap();
+        assert!(errs.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].package, package("extra", "0.3.0"));
+        assert_eq!(
+            diff.added[0].dependents[0].features,
+            BTreeSet::from(["more".to_string()])
+        );
+        assert_eq!(diff.upgraded.len(), 1);
+        assert_eq!(
+            (
+                diff.upgraded[0].from.as_slice(),
+                diff.upgraded[0].to.as_slice()
+            ),
+            (
+                ["1.0.0".to_string()].as_slice(),
+                ["2.0.0".to_string()].as_slice()
+            )
+        );
+    }
+
+    #[test]
+    fn resolve_many() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0").publish(&registry);
+        TestPackage::new("a", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .publish(&registry);
+        TestPackage::new("b", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("extra", "^0.3"))
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let batch = resolver
+            .resolve_many(&[
+                ResolveRequest::new("a", None),
+                ResolveRequest::new("missing", None),
+                ResolveRequest::new("b", None),
+            ])
+            .unwrap();
+        assert_eq!(batch.results.len(), 3);
+        assert!(batch.results[0].as_ref().unwrap().is_empty());
+        assert!(matches!(
+            batch.results[1],
+            Err(Error::PackageNotFound { .. })
+        ));
+        assert!(batch.results[2].as_ref().unwrap().is_empty());
+
+        let (a, b) = (package("a", "0.1.0"), package("b", "0.1.0"));
+        assert_eq!(batch.graph.roots().collect::<Vec<_>>(), [&a, &b]);
+        assert_eq!(
+            batch.required_by[&package("leaf", "1.0.0")],
+            BTreeSet::from([a.clone(), b.clone()])
+        );
+        assert_eq!(
+            batch.required_by[&package("extra", "0.3.0")],
+            BTreeSet::from([b.clone()])
+        );
+        assert_eq!(batch.required_by[&a], BTreeSet::from([a.clone()]));
+    }
+
+    /// Publish packages for the parallel resolution tests, and get requests
+    /// for them, including one that can't be resolved.
+    fn parallel_requests(registry: &TestRegistry) -> Vec<ResolveRequest> {
+        TestPackage::new("leaf", "1.0.0").publish(registry);
+        TestPackage::new("leaf", "1.2.0").publish(registry);
+        TestPackage::new("tool", "0.1.0").publish(registry);
+        TestPackage::new("test-only", "0.4.0")
+            .dep(TestDep::new("tool", "^0.1"))
+            .publish(registry);
+        TestPackage::new("a", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("tool", "^0.1").build())
+            .publish(registry);
+        TestPackage::new("b", "0.1.0")
+            .dep(TestDep::new("leaf", "=1.0.0"))
+            .dep(TestDep::new("test-only", "^0.4").dev())
+            .publish(registry);
+        TestPackage::new("c", "0.2.0")
+            .dep(TestDep::new("a", "^0.1"))
+            .publish(registry);
+
+        let mut with_dev_deps = ResolveRequest::new("b", None);
+        with_dev_deps.dev_dependencies = true;
+        vec![
+            ResolveRequest::new("a", None),
+            ResolveRequest::new("missing", None),
+            with_dev_deps,
+            ResolveRequest::new("b", Some("0.1")),
+            ResolveRequest::new("c", None),
+        ]
+    }
+
+    fn assert_same_batch(parallel: &BatchResolution, serial: &BatchResolution) {
+        assert_eq!(parallel.graph, serial.graph);
+        assert_eq!(parallel.required_by, serial.required_by);
+        let outcomes = |batch: &BatchResolution| {
+            batch
+                .results
+                .iter()
+                .map(|r| r.as_ref().map(Vec::len).map_err(ToString::to_string))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(outcomes(parallel), outcomes(serial));
+    }
+
+    #[test]
+    fn resolve_parallel() {
+        let registry = TestRegistry::new();
+        let requests = parallel_requests(&registry);
+
+        let cargo_home = tempfile::tempdir().unwrap();
+        let builder = Resolver::builder()
+            .local_registry(registry.path())
+            .cargo_home(cargo_home.path());
+        let parallel = builder.resolve_parallel(&requests, 3).unwrap();
+        assert!(matches!(
+            parallel.results[1],
+            Err(Error::PackageNotFound { .. })
+        ));
+        assert_eq!(
+            parallel.required_by[&package("test-only", "0.4.0")],
+            BTreeSet::from([package("b", "0.1.0")])
+        );
+
+        let config = builder.config().unwrap();
+        let mut serial = builder.clone().build(&config).unwrap();
+        assert_same_batch(&parallel, &serial.resolve_many(&requests).unwrap());
+    }
+
+    #[test]
+    fn resolve_parallel_sparse() {
+        let registry = TestRegistry::new();
+        let requests = parallel_requests(&registry);
+        let url = registry.serve_sparse();
+
+        let cargo_home = tempfile::tempdir().unwrap();
+        let builder = Resolver::builder()
+            .index_url(&url)
+            .cargo_home(cargo_home.path());
+        let parallel = builder.resolve_parallel(&requests, 4).unwrap();
+
+        let config = builder.config().unwrap();
+        let mut serial = builder.clone().build(&config).unwrap();
+        assert_same_batch(&parallel, &serial.resolve_many(&requests).unwrap());
+    }
+
+    #[test]
+    fn why() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.1.0").publish(&registry);
+        TestPackage::new("extra", "0.3.0")
+            .dep(TestDep::new("leaf", "^1.1"))
+            .publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("extra", "^0.3").optional())
+            .feature("more", &["dep:extra"])
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (paths, errs) = resolver.why("root", None, "leaf").unwrap();
+        assert!(errs.is_empty());
+        let hops = paths
+            .iter()
+            .map(|path| {
+                path.iter()
+                    .map(|e| {
+                        let features = e.features.iter().cloned().collect::<Vec<_>>();
+                        (e.to.name.as_str(), e.version_req.as_str(), features)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hops,
+            [
+                vec![
+                    ("extra", "^0.3", vec!["more".to_string()]),
+                    ("leaf", "^1.1", vec![])
+                ],
+                vec![("leaf", "^1", vec![])],
+            ]
+        );
+
+        let (paths, _) = resolver.why("root", None, "missing").unwrap();
+        assert!(paths.is_empty());
+    }
+
+    #[test]
+    fn why_dep_features() {
+        let registry = TestRegistry::new();
+        TestPackage::new("simd", "1.0.0").publish(&registry);
+        TestPackage::new("mid", "1.0.0")
+            .dep(TestDep::new("simd", "^1").optional())
+            .feature("fast", &["dep:simd"])
+            .publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("mid", "^1"))
+            .feature("fast", &["mid/fast"])
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (paths, errs) = resolver.why("root", None, "simd").unwrap();
+        assert!(errs.is_empty());
+        let [path] = &paths[..] else {
+            panic!("expected a single path: {paths:?}");
+        };
+        let hops = path
+            .iter()
+            .map(|e| (e.to.name.as_str(), e.optional, e.features.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hops,
+            [
+                ("mid", false, BTreeSet::from(["fast".to_string()])),
+                ("simd", true, BTreeSet::from(["fast".to_string()])),
+            ]
+        );
+    }
+
+    #[test]
+    fn feature_attribution() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("ring", "0.17.0").publish(&registry);
+        TestPackage::new("rustls", "0.23.0")
+            .dep(TestDep::new("ring", "^0.17"))
+            .publish(&registry);
+        TestPackage::new("json", "1.0.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("rustls", "^0.23").optional())
+            .dep(TestDep::new("json", "^1").optional())
+            .feature("default", &["std"])
+            .feature("std", &[])
+            .feature("tls", &["dep:rustls"])
+            .feature("full", &["tls", "dep:json"])
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (deps, errs) = resolver.feature_dependencies("root", None).unwrap();
+        assert!(errs.is_empty());
+        assert_eq!(
+            deps.baseline,
+            HashSet::from([package("root", "0.1.0"), package("leaf", "1.0.0")])
+        );
+        assert_eq!(
+            deps.features.keys().collect::<Vec<_>>(),
+            ["default", "full", "std", "tls"]
+        );
+        assert!(deps.features["default"].is_empty());
+        assert!(deps.features["std"].is_empty());
+        assert_eq!(
+            deps.features["tls"],
+            HashSet::from([package("rustls", "0.23.0"), package("ring", "0.17.0")])
+        );
+        assert_eq!(
+            deps.features["full"],
+            HashSet::from([
+                package("rustls", "0.23.0"),
+                package("ring", "0.17.0"),
+                package("json", "1.0.0"),
+            ])
+        );
+    }
+
+    #[test]
+    fn resolve_request_features() {
+        let registry = TestRegistry::new();
+        TestPackage::new("std-dep", "1.0.0").publish(&registry);
+        TestPackage::new("tls-dep", "1.0.0").publish(&registry);
+        TestPackage::new("json-dep", "1.0.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("std-dep", "^1").optional())
+            .dep(TestDep::new("tls-dep", "^1").optional())
+            .dep(TestDep::new("json-dep", "^1").optional())
+            .feature("default", &["std"])
+            .feature("std", &["dep:std-dep"])
+            .feature("tls", &["dep:tls-dep"])
+            .feature("json", &["dep:json-dep"])
+            .publish(&registry);
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let mut resolve = |request: ResolveRequest| {
+            let (graph, errs) = resolver.resolve(&request).unwrap();
+            assert!(errs.is_empty());
+            graph
+                .packages()
+                .map(|p| p.name.clone())
+                .collect::<BTreeSet<_>>()
+        };
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<BTreeSet<_>>();
+
+        assert_eq!(
+            resolve(ResolveRequest::new("root", None)),
+            names(&["root", "std-dep", "tls-dep", "json-dep"])
+        );
+        assert_eq!(
+            resolve(ResolveRequest::new("root", None).features(Vec::<String>::new())),
+            names(&["root", "std-dep"])
+        );
+        assert_eq!(
+            resolve(
+                ResolveRequest::new("root", None)
+                    .no_default_features()
+                    .features(["tls"])
+            ),
+            names(&["root", "tls-dep"])
+        );
+        assert_eq!(
+            resolve(
+                ResolveRequest::new("root", None)
+                    .no_default_features()
+                    .all_features()
+            ),
+            names(&["root", "std-dep", "tls-dep", "json-dep"])
+        );
+        assert_eq!(
+            resolve(ResolveRequest::new("root", None).no_default_features()),
+            names(&["root", "std-dep", "tls-dep", "json-dep"])
+        );
+    }
+
+    #[test]
+    fn resolve_request_unknown_feature() {
+        let registry = TestRegistry::new();
+        TestPackage::new("root", "0.1.0").publish(&registry);
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        assert!(resolver
+            .resolve(&ResolveRequest::new("root", None).features(["nope"]))
+            .is_err());
+    }
+
+    #[test]
+    fn target_filtering() {
+        let registry = TestRegistry::new();
+        for name in ["leaf", "libc", "windows-sys", "wasm-bindgen", "unstable"] {
+            TestPackage::new(name, "1.0.0").publish(&registry);
+        }
+        TestPackage::new("sys-helper", "1.0.0")
+            .dep(TestDep::new("libc", "^1").target("cfg(unix)"))
+            .dep(TestDep::new("windows-sys", "^1").target("cfg(windows)"))
+            .publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("sys-helper", "^1"))
+            .dep(TestDep::new("wasm-bindgen", "^1").target("wasm32-unknown-unknown"))
+            .dep(TestDep::new("unstable", "^1").target("cfg(all(unix, tokio_unstable))"))
+            .publish(&registry);
+
+        // Filtering downloads the packages to find proc-macros.
+        let cargo_home = tempfile::tempdir().unwrap();
+        let names = |builder: ResolverBuilder| {
+            let builder = builder
+                .local_registry(registry.path())
+                .cargo_home(cargo_home.path());
+            let config = builder.config().unwrap();
+            let mut resolver = builder.build(&config).unwrap();
+            let (deps, errs) = resolver.dependencies("root", None).unwrap();
+            assert!(errs.is_empty());
+            let mut names = deps.into_iter().map(|p| p.name).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            names(Resolver::builder()),
+            [
+                "leaf",
+                "libc",
+                "root",
+                "sys-helper",
+                "unstable",
+                "wasm-bindgen",
+                "windows-sys"
+            ]
+        );
+        assert_eq!(
+            names(Resolver::builder().target("x86_64-unknown-linux-gnu")),
+            ["leaf", "libc", "root", "sys-helper"]
+        );
+        assert_eq!(
+            names(
+                Resolver::builder()
+                    .target("x86_64-unknown-linux-gnu")
+                    .cfg("tokio_unstable")
+            ),
+            ["leaf", "libc", "root", "sys-helper", "unstable"]
+        );
+        assert_eq!(
+            names(
+                Resolver::builder()
+                    .target("x86_64-pc-windows-msvc")
+                    .target("wasm32-unknown-unknown")
+            ),
+            ["leaf", "root", "sys-helper", "wasm-bindgen", "windows-sys"]
+        );
+
+        let builder = Resolver::builder()
+            .local_registry(registry.path())
+            .cargo_home(cargo_home.path())
+            .target("x86_64-unknown-linux-gnu");
+        let config = builder.config().unwrap();
+        let mut resolver = builder.build(&config).unwrap();
+        let (graph, _) = resolver.dependency_graph("root", None).unwrap();
+        let edge = graph.edges().find(|e| e.to.name == "libc").unwrap();
+        assert_eq!(edge.target.as_deref(), Some("cfg(unix)"));
+    }
+
+    #[test]
+    fn host_dependencies() {
+        let registry = TestRegistry::new();
+        for name in ["libc", "unix-helper", "unix-build", "wasm-only"] {
+            TestPackage::new(name, "1.0.0").publish(&registry);
+        }
+        TestPackage::new("cc", "1.0.0")
+            .dep(TestDep::new("libc", "^1").target("cfg(unix)"))
+            .publish(&registry);
+        TestPackage::new("derive", "1.0.0")
+            .dep(TestDep::new("unix-helper", "^1").target("cfg(unix)"))
+            .proc_macro()
+            .publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("cc", "^1").build())
+            .dep(TestDep::new("unix-build", "^1").build().target("cfg(unix)"))
+            .dep(TestDep::new("derive", "^1"))
+            .dep(TestDep::new("wasm-only", "^1").target(r#"cfg(target_arch = "wasm32")"#))
+            .publish(&registry);
+
+        // Build dependencies and proc-macros are evaluated against the host,
+        // like `cargo tree --target`.
+        let cargo_home = tempfile::tempdir().unwrap();
+        let names = |builder: ResolverBuilder| {
+            let builder = builder
+                .local_registry(registry.path())
+                .cargo_home(cargo_home.path())
+                .target("wasm32-unknown-unknown");
+            let config = builder.config().unwrap();
+            let mut resolver = builder.build(&config).unwrap();
+            let (deps, errs) = resolver.dependencies("root", None).unwrap();
+            assert!(errs.is_empty());
+            let mut names = deps.into_iter().map(|p| p.name).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(Resolver::builder().host("x86_64-unknown-linux-gnu")),
+            [
+                "cc",
+                "derive",
+                "libc",
+                "root",
+                "unix-build",
+                "unix-helper",
+                "wasm-only"
+            ]
+        );
+        assert_eq!(
+            names(Resolver::builder().host("x86_64-pc-windows-msvc")),
+            ["cc", "derive", "root", "wasm-only"]
+        );
+        assert_eq!(
+            names(Resolver::builder()),
+            names(Resolver::builder().host(DEFAULT_HOST))
+        );
+    }
+
+    #[test]
+    fn unknown_target() {
+        let registry = TestRegistry::new();
+        let config = Config::default().unwrap();
+        assert!(matches!(
+            Resolver::builder()
+                .local_registry(registry.path())
+                .target("x86_64-unknown-nowhere")
+                .build(&config),
+            Err(Error::UnknownTarget(_))
+        ));
+    }
+
+    #[test]
+    fn dependency_kinds() {
+        let registry = TestRegistry::new();
+        for name in ["leaf", "cc", "test-util", "test-leaf"] {
+            TestPackage::new(name, "1.0.0").publish(&registry);
+        }
+        TestPackage::new("gen", "1.0.0")
+            .dep(TestDep::new("cc", "^1"))
+            .publish(&registry);
+        TestPackage::new("mock", "1.0.0")
+            .dep(TestDep::new("test-leaf", "^1"))
+            .dep(TestDep::new("test-util", "^1").dev())
+            .publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("gen", "^1").build())
+            .dep(TestDep::new("mock", "^1").dev())
+            .publish(&registry);
+        TestPackage::new("root", "0.2.0").publish(&registry);
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let mut resolve = |request: ResolveRequest| {
+            let (graph, errs) = resolver.resolve(&request).unwrap();
+            assert!(errs.is_empty());
+            graph
+                .kinds()
+                .into_iter()
+                .map(|(p, kinds)| (p.name.clone(), kinds.into_iter().collect::<Vec<_>>()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let kinds = resolve(ResolveRequest::new("root", Some("0.1")));
+        assert_eq!(
+            kinds.keys().collect::<Vec<_>>(),
+            ["cc", "gen", "leaf", "root"]
+        );
+        assert_eq!(kinds["root"], [DepKind::Normal]);
+        assert_eq!(kinds["leaf"], [DepKind::Normal]);
+        assert_eq!(kinds["gen"], [DepKind::Build]);
+        assert_eq!(kinds["cc"], [DepKind::Build]);
+
+        let kinds = resolve(ResolveRequest::new("root", Some("0.1")).no_build_dependencies());
+        assert_eq!(kinds.keys().collect::<Vec<_>>(), ["leaf", "root"]);
+
+        let kinds = resolve(ResolveRequest::new("root", Some("0.1")).dev_dependencies());
+        assert_eq!(
+            kinds.keys().collect::<Vec<_>>(),
+            ["cc", "gen", "leaf", "mock", "root", "test-leaf"]
+        );
+        assert_eq!(kinds["root"], [DepKind::Normal]);
+        assert_eq!(kinds["mock"], [DepKind::Development]);
+        assert_eq!(kinds["test-leaf"], [DepKind::Development]);
+    }
+
+    #[test]
+    fn package_metadata() {
+        let registry = TestRegistry::new();
+        let sys = TestPackage::new("foo-sys", "0.2.1")
+            .links("foo")
+            .rust_version("1.70")
+            .feature("static", &[])
+            .feature("bundled", &[])
+            .publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("foo-sys", "^0.2").features(&["static"]))
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (graph, _) = resolver.dependency_graph("root", None).unwrap();
+        let resolved = graph.package(&package("foo-sys", "0.2.1")).unwrap();
+        assert_eq!(resolved.checksum, Some(sys.checksum()));
+        assert_eq!(resolved.links.as_deref(), Some("foo"));
+        assert_eq!(resolved.rust_version.as_deref(), Some("1.70"));
+        assert_eq!(resolved.features, BTreeSet::from(["static".to_string()]));
+        assert!(resolved.source.starts_with("local-registry+file://"));
+
+        let root = graph.package(&package("root", "0.1.0")).unwrap();
+        assert_eq!(root.links, None);
+        assert_eq!(root.rust_version, None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_results() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1").optional())
+            .dep(TestDep::new("missing", "^1").optional())
+            .feature("leaf", &["dep:leaf"])
+            .feature("broken", &["dep:missing"])
+            .publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        let (deps, errs) = resolver.feature_dependencies("root", None).unwrap();
+        assert_eq!(
+            serde_json::to_value(&deps).unwrap(),
+            serde_json::json!({
+                "baseline": [{"name": "root", "version": "0.1.0"}],
+                "features": {
+                    "leaf": [{"name": "leaf", "version": "1.0.0"}],
+                },
+            })
+        );
+
+        assert_eq!(errs.len(), 1);
+        let json = serde_json::to_value(&errs[0]).unwrap();
+        assert_eq!(json["name"], "broken");
+        assert!(json["error"].as_str().unwrap().contains("missing"));
+    }
+
+    #[test]
+    fn local_registry_missing_package() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::with_local_registry(&config, registry.path()).unwrap();
+        assert!(matches!(
+            resolver.dependencies("leaf", Some("2")),
+            Err(Error::PackageNotFound { .. })
+        ));
+        assert!(matches!(
+            resolver.dependencies("missing", None),
+            Err(Error::PackageNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn sparse_registry_url() {
+        let registry = TestRegistry::new();
+        TestPackage::new("internal-leaf", "0.2.0").publish(&registry);
+        TestPackage::new("internal", "1.0.0")
+            .dep(TestDep::new("internal-leaf", "^0.2"))
+            .publish(&registry);
+        let url = registry.serve_sparse();
+
+        let cargo_home = tempfile::tempdir().unwrap();
+        let builder = Resolver::builder()
+            .index_url(&url)
+            .cargo_home(cargo_home.path());
+        let config = builder.config().unwrap();
+        let mut resolver = builder.build(&config).unwrap();
+        let (deps, errs) = resolver.dependencies("internal", None).unwrap();
+        assert!(errs.is_empty());
+        assert_eq!(
+            deps,
+            HashSet::from([
+                package("internal", "1.0.0"),
+                package("internal-leaf", "0.2.0"),
+            ])
+        );
+    }
+
+    #[test]
+    fn sparse_registry_named() {
+        let registry = TestRegistry::new();
+        TestPackage::new("internal", "1.0.0").publish(&registry);
+        let url = registry.serve_sparse();
+
+        let cwd = tempfile::tempdir().unwrap();
+        std::fs::create_dir(cwd.path().join(".cargo")).unwrap();
+        std::fs::write(
+            cwd.path().join(".cargo/config.toml"),
+            format!("[registries.internal]\nindex = \"{url}\"\n"),
+        )
+        .unwrap();
+
+        let cargo_home = tempfile::tempdir().unwrap();
+        let builder = Resolver::builder()
+            .registry("internal")
+            .cwd(cwd.path())
+            .cargo_home(cargo_home.path());
+        let config = builder.config().unwrap();
+        let mut resolver = builder.build(&config).unwrap();
+        let (deps, errs) = resolver.dependencies("internal", Some("1")).unwrap();
+        assert!(errs.is_empty());
+        assert_eq!(deps, HashSet::from([package("internal", "1.0.0")]));
+    }
+
+    #[test]
+    fn unknown_registry_name() {
+        let cwd = tempfile::tempdir().unwrap();
+        let builder = Resolver::builder()
+            .registry("no-such-registry")
+            .cwd(cwd.path());
+        let config = builder.config().unwrap();
+        assert!(builder.build(&config).is_err());
+    }
+
+    #[test]
+    fn licenses() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0")
+            .license("MIT OR Apache-2.0")
+            .publish(&registry);
+        TestPackage::new("unlicensed", "1.0.0").publish(&registry);
+        TestPackage::new("root", "0.1.0")
+            .dep(TestDep::new("leaf", "^1"))
+            .dep(TestDep::new("unlicensed", "^1"))
+            .license("MIT")
+            .publish(&registry);
+        let cargo_home = tempfile::tempdir().unwrap();
+
+        let builder = Resolver::builder()
+            .local_registry(registry.path())
+            .cargo_home(cargo_home.path());
+        let config = builder.config().unwrap();
+        let mut resolver = builder.build(&config).unwrap();
+        let (graph, _) = resolver.dependency_graph("root", None).unwrap();
+        assert_eq!(
+            resolver.licenses(&graph).unwrap(),
+            BTreeMap::from([
+                (package("leaf", "1.0.0"), "MIT OR Apache-2.0".to_string()),
+                (package("root", "0.1.0"), "MIT".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn local_registry_invalid_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::default().unwrap();
+        assert!(matches!(
+            Resolver::with_local_registry(&config, dir.path()),
+            Err(Error::InvalidRegistry(_))
+        ));
+    }
+
+    #[test]
+    fn local_registry_relative_path() {
+        let registry = TestRegistry::new();
+        TestPackage::new("leaf", "1.0.0").publish(&registry);
+        let (parent, name) = (
+            registry.path().parent().unwrap(),
+            registry.path().file_name(),
+        );
+
+        let builder = Resolver::builder()
+            .local_registry(name.unwrap())
+            .cwd(parent);
+        let config = builder.config().unwrap();
+        let mut resolver = builder.build(&config).unwrap();
+        let (deps, errs) = resolver.dependencies("leaf", None).unwrap();
+        assert!(errs.is_empty());
+        assert_eq!(deps, HashSet::from([package("leaf", "1.0.0")]));
+    }
 
     #[test]
     fn async_std_latest() {
-        let mut resolver = Resolver::new().unwrap();
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::new(&config).unwrap();
         let (deps, errs) = resolver.dependencies("async-std", None).unwrap();
         eprintln!("{deps:#?}");
         eprintln!("{errs:#?}");
@@ -255,7 +2677,8 @@ mod tests {
 
     #[test]
     fn cargo_latest() {
-        let mut resolver = Resolver::new().unwrap();
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::new(&config).unwrap();
         let (deps, errs) = resolver.dependencies("cargo", None).unwrap();
         assert!(errs.is_empty());
         eprintln!("{deps:#?}");
@@ -263,7 +2686,8 @@ mod tests {
 
     #[test]
     fn serde_versioned() {
-        let mut resolver = Resolver::new().unwrap();
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::new(&config).unwrap();
         let (deps, errs) = resolver.dependencies("serde", Some("1.0.164")).unwrap();
         assert!(errs.is_empty());
         eprintln!("{deps:#?}");
@@ -271,7 +2695,8 @@ mod tests {
 
     #[test]
     fn serde_latest() {
-        let mut resolver = Resolver::new().unwrap();
+        let config = Config::default().unwrap();
+        let mut resolver = Resolver::new(&config).unwrap();
         let (deps, errs) = resolver.dependencies("serde", None).unwrap();
         assert!(errs.is_empty());
         eprintln!("{deps:#?}");
@@ -0,0 +1,150 @@
+//! `Cargo.lock` files.
+
+use anyhow::anyhow;
+use cargo::core::package_id::PackageId;
+use cargo::core::resolver::{Resolve, ResolveVersion};
+
+use crate::{Error, Package, Result, CRATES_IO_SOURCES};
+
+/// The format version of a `Cargo.lock` file.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum LockfileVersion {
+    /// Version 3, understood by Cargo 1.53 and later.
+    #[default]
+    V3,
+    /// Version 4, understood by Cargo 1.78 and later.
+    V4,
+}
+
+/// Encode a resolve as a `Cargo.lock` file, in the same layout Cargo writes.
+///
+/// `dummy` is the package whose dependencies were resolved, and `root` is
+/// the package it was resolved for. The dummy package is left out, and its
+/// other dependencies (the root's dev-dependencies) are attributed to the
+/// root. The root is listed like a workspace member, without a source or
+/// checksum, so the lockfile can be used to build it from its source.
+///
+/// Local registries can only stand in for another registry, so packages from
+/// one are listed as coming from crates.io, like Cargo does when replacing a
+/// source.
+pub(crate) fn encode(
+    resolve: &mut Resolve,
+    dummy: PackageId,
+    root: PackageId,
+    version: LockfileVersion,
+) -> Result<String> {
+    resolve.set_version(match version {
+        LockfileVersion::V3 => ResolveVersion::V3,
+        LockfileVersion::V4 => ResolveVersion::V4,
+    });
+    let toml = toml::Table::try_from(&*resolve).map_err(|e| anyhow!(e))?;
+    let mut packages = toml["package"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p.as_table().cloned())
+        .collect::<Vec<_>>();
+
+    let is_package = |package: &toml::Table, id: PackageId| {
+        package["name"].as_str() == Some(id.name().as_str())
+            && package["version"].as_str() == Some(&id.version().to_string())
+    };
+    let dummy_index = packages.iter().position(|p| is_package(p, dummy));
+    let dummy_deps = match dummy_index.map(|i| packages.remove(i)) {
+        Some(mut dummy) => match dummy.remove("dependencies") {
+            Some(toml::Value::Array(deps)) => deps,
+            _ => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+    let is_root = |dep: &toml::Value| {
+        let mut parts = dep.as_str().unwrap_or_default().split(' ');
+        parts.next() == Some(root.name().as_str())
+            && parts
+                .next()
+                .map_or(true, |v| v == root.version().to_string())
+    };
+    if let Some(package) = packages.iter_mut().find(|p| is_package(p, root)) {
+        package.remove("source");
+        package.remove("checksum");
+        let deps = package
+            .entry("dependencies")
+            .or_insert_with(|| toml::Value::Array(Vec::new()));
+        if let toml::Value::Array(deps) = deps {
+            deps.extend(dummy_deps.into_iter().filter(|d| !is_root(d)));
+            deps.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+            deps.dedup();
+        }
+    }
+
+    for package in &mut packages {
+        let local = package
+            .get("source")
+            .and_then(|s| s.as_str())
+            .is_some_and(|s| s.starts_with("local-registry+"));
+        if local {
+            package.insert("source".to_string(), CRATES_IO_SOURCES[0].into());
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str("# This file is automatically @generated by Cargo.\n");
+    out.push_str("# It is not intended for manual editing.\n");
+    if let Some(version) = toml.get("version") {
+        out.push_str(&format!("version = {version}\n\n"));
+    }
+    for package in &packages {
+        out.push_str("[[package]]\n");
+        for key in ["name", "version", "source", "checksum"] {
+            if let Some(value) = package.get(key) {
+                out.push_str(&format!("{key} = {value}\n"));
+            }
+        }
+        let deps = package
+            .get("dependencies")
+            .and_then(|d| d.as_array())
+            .filter(|d| !d.is_empty());
+        if let Some(deps) = deps {
+            out.push_str("dependencies = [\n");
+            for dep in deps {
+                out.push_str(&format!(" {dep},\n"));
+            }
+            out.push_str("]\n");
+        }
+        out.push('\n');
+    }
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    Ok(out)
+}
+
+/// Decode the packages a `Cargo.lock` file pins, with their sources. Path
+/// dependencies and workspace members have no source and are left out.
+pub(crate) fn decode(contents: &str) -> Result<Vec<(Package, String)>> {
+    let toml = contents
+        .parse::<toml::Table>()
+        .map_err(|e| Error::InvalidLockfile(e.message().to_string()))?;
+    let mut packages = Vec::new();
+    for package in toml
+        .get("package")
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let field = |key| package.get(key).and_then(|v| v.as_str());
+        let (Some(name), Some(version)) = (field("name"), field("version")) else {
+            return Err(Error::InvalidLockfile(format!(
+                "package without a name and version: {package}"
+            )));
+        };
+        if let Some(source) = field("source") {
+            let package = Package {
+                name: name.to_string(),
+                version: version.to_string(),
+            };
+            packages.push((package, source.to_string()));
+        }
+    }
+    Ok(packages)
+}
@@ -0,0 +1,705 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+use crate_deps::UnresolvedFeature;
+use crate_deps::{
+    CycloneDx, DepKind, DependencyGraph, Diagram, Edge, GraphDiff, LockfileVersion, Package,
+    ResolveRequest, Resolver, ResolverBuilder, Spdx, Tree, VersionStrategy, WorkspaceRequest,
+};
+
+/// Compute the dependency tree of crates from a registry.
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Cli {
+    /// Crates to resolve, as `name` or `name@version`.
+    #[arg(
+        value_name = "CRATE[@VERSION]",
+        required_unless_present = "manifest_path"
+    )]
+    crates: Vec<String>,
+
+    /// Also resolve the members of the local workspace with this
+    /// `Cargo.toml`.
+    #[arg(long, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+
+    /// Features to enable, separated by commas or spaces. By default, every
+    /// feature is enabled in turn and the results are merged.
+    #[arg(short = 'F', long, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Enable all features at once.
+    #[arg(long, conflicts_with = "features")]
+    all_features: bool,
+
+    /// Don't enable the default features.
+    #[arg(long)]
+    no_default_features: bool,
+
+    /// Leave out build dependencies.
+    #[arg(long)]
+    no_build_deps: bool,
+
+    /// Include the dev-dependencies of the requested crates.
+    #[arg(long)]
+    dev_deps: bool,
+
+    /// Only include dependencies for the target triple. May be given more
+    /// than once.
+    #[arg(long, value_name = "TRIPLE")]
+    target: Vec<String>,
+
+    /// An additional cfg to set for every target, e.g. `tokio_unstable`.
+    #[arg(long)]
+    cfg: Vec<String>,
+
+    /// The host triple that build dependencies and proc-macros are built
+    /// for, with `--target`. Defaults to the triple of this binary.
+    #[arg(long, value_name = "TRIPLE")]
+    host: Option<String>,
+
+    /// Registry to use, as configured in the Cargo config.
+    #[arg(long, conflicts_with_all = ["index", "local_registry"])]
+    registry: Option<String>,
+
+    /// Registry index URL to use. Use a `sparse+` prefix for sparse indexes.
+    #[arg(long, conflicts_with = "local_registry")]
+    index: Option<String>,
+
+    /// Local registry (created by `cargo local-registry`) to use.
+    #[arg(long, value_name = "PATH")]
+    local_registry: Option<String>,
+
+    /// Prefer the versions pinned by this `Cargo.lock` when resolving crates.
+    #[arg(long, value_name = "PATH")]
+    lockfile: Option<PathBuf>,
+
+    /// Ignore the workspace's `Cargo.lock` when resolving `--manifest-path`.
+    #[arg(long)]
+    no_lockfile: bool,
+
+    /// Which versions of dependencies to choose.
+    #[arg(long, value_enum, default_value_t = Versions::Maximum)]
+    versions: Versions,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Format::List)]
+    format: Format,
+
+    /// The `Cargo.lock` format version to write.
+    #[arg(long, value_name = "VERSION", default_value_t = 3, value_parser = clap::value_parser!(u8).range(3..=4))]
+    lockfile_version: u8,
+
+    /// Maximum depth of the tree.
+    #[arg(long)]
+    depth: Option<usize>,
+
+    /// Print the packages that depend on this package in the tree.
+    #[arg(short, long, value_name = "CRATE[@VERSION]")]
+    invert: Option<String>,
+
+    /// Print the packages that depend on each crate with more than one version
+    /// in the tree.
+    #[arg(short, long, conflicts_with = "invert")]
+    duplicates: bool,
+
+    /// Print every path from the requested crates to this crate instead of
+    /// the dependencies.
+    #[arg(long, value_name = "CRATE")]
+    why: Option<String>,
+
+    /// Resolve every release that matches each crate's version requirement,
+    /// and print each of them in the list, tree or duplicates format.
+    #[arg(long, conflicts_with_all = ["manifest_path", "why"])]
+    all_versions: bool,
+
+    /// Compare the dependencies of the crate with those of this version of
+    /// it, printing the packages that were added, removed or upgraded.
+    #[arg(
+        long,
+        value_name = "VERSION",
+        conflicts_with_all = ["manifest_path", "why", "all_versions"]
+    )]
+    diff: Option<String>,
+
+    /// Color build and dev-dependencies in diagrams.
+    #[arg(long)]
+    color_kinds: bool,
+
+    /// Group multiple versions of a crate into a cluster in diagrams.
+    #[arg(long)]
+    cluster_versions: bool,
+
+    /// Label edges with their activating features in diagrams.
+    #[arg(long)]
+    edge_features: bool,
+
+    /// Don't download the crates to read their licenses for the CycloneDX and
+    /// SPDX formats. Their licenses are left out instead.
+    #[arg(long)]
+    no_licenses: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum Versions {
+    /// The newest compatible versions.
+    Maximum,
+    /// The oldest compatible versions.
+    Minimal,
+    /// The oldest compatible versions of direct dependencies, and the newest
+    /// versions of everything else.
+    DirectMinimal,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum Format {
+    /// Every resolved package, one per line.
+    List,
+    /// The dependency tree of each crate.
+    Tree,
+    /// Crates with more than one version, and the packages that require each
+    /// version.
+    Duplicates,
+    /// A `Cargo.lock` pinning the dependencies of a single crate, with all of
+    /// its features and dev-dependencies, like Cargo locks a workspace.
+    Lockfile,
+    /// A Graphviz DOT diagram.
+    Dot,
+    /// A Mermaid flowchart.
+    Mermaid,
+    /// The dependency graph and unresolved features as JSON.
+    Json,
+    /// A CycloneDX 1.5 bill of materials in JSON.
+    CyclonedxJson,
+    /// A CycloneDX 1.5 bill of materials in XML.
+    CyclonedxXml,
+    /// An SPDX 2.3 document in the tag-value format.
+    Spdx,
+    /// An SPDX 2.3 document in JSON.
+    SpdxJson,
+}
+
+/// The JSON output of the binary.
+#[derive(serde::Serialize)]
+struct JsonOutput<'a> {
+    graph: &'a DependencyGraph,
+    unresolved_features: Vec<JsonUnresolvedFeature<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonUnresolvedFeature<'a> {
+    /// The crate spec the feature belongs to.
+    #[serde(rename = "crate")]
+    spec: &'a str,
+    #[serde(flatten)]
+    feature: &'a UnresolvedFeature,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Resolve and print the requested crates, returning whether every feature
+/// could be resolved.
+fn run(cli: &Cli) -> anyhow::Result<bool> {
+    let builder = builder(cli);
+    let config = builder.config()?;
+    let mut resolver = builder.build(&config)?;
+    if cli.format == Format::Lockfile {
+        let [spec] = &cli.crates[..] else {
+            anyhow::bail!("the lockfile format takes a single crate");
+        };
+        let version = match cli.lockfile_version {
+            3 => LockfileVersion::V3,
+            _ => LockfileVersion::V4,
+        };
+        println!("{}", resolver.lockfile(&request(cli, spec), version)?);
+        return Ok(true);
+    }
+    if cli.all_versions {
+        return write_versions(cli, &mut resolver);
+    }
+    if let Some(version) = &cli.diff {
+        let [spec] = &cli.crates[..] else {
+            anyhow::bail!("--diff takes a single crate");
+        };
+        let old = request(cli, spec);
+        let mut new = old.clone();
+        new.version = Some(version.clone());
+        let (diff, unresolved_features) = resolver.diff(&old, &new)?;
+        for err in &unresolved_features {
+            eprintln!(
+                "warning: {spec}: couldn't resolve dependencies with feature '{}': {:#}",
+                err.name, err.error
+            );
+        }
+        let mut stdout = io::stdout().lock();
+        match cli.format {
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut stdout, &diff)?;
+                writeln!(stdout)?;
+            }
+            Format::List => write_diff(&mut stdout, &diff)?,
+            _ => anyhow::bail!("--diff only supports the list and json formats"),
+        }
+        return Ok(unresolved_features.is_empty());
+    }
+
+    let mut graph = DependencyGraph::new();
+    if let Some(path) = &cli.manifest_path {
+        resolver.merge_workspace(&workspace_request(cli, path), &mut graph)?;
+    }
+    let requests = cli
+        .crates
+        .iter()
+        .map(|spec| request(cli, spec))
+        .collect::<Vec<_>>();
+    let batch = resolver.resolve_many(&requests)?;
+    graph.merge(batch.graph);
+    let mut unresolved_features = Vec::new();
+    for (spec, result) in cli.crates.iter().zip(batch.results) {
+        for err in result? {
+            eprintln!(
+                "warning: {spec}: couldn't resolve dependencies with feature '{}': {:#}",
+                err.name, err.error
+            );
+            unresolved_features.push((spec.as_str(), err));
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    if let Some(target) = &cli.why {
+        write_paths(&mut stdout, &graph, target)?;
+        return Ok(unresolved_features.is_empty());
+    }
+    let licenses = licenses(cli, &mut resolver, &graph);
+    match cli.format {
+        Format::List => write_list(&mut stdout, &graph)?,
+        Format::Tree => tree(cli, &graph)?.write(&mut stdout)?,
+        Format::Duplicates => write_duplicates(&mut stdout, &graph)?,
+        Format::Lockfile => unreachable!(),
+        Format::Dot => diagram(cli, &graph).write_dot(&mut stdout)?,
+        Format::Mermaid => diagram(cli, &graph).write_mermaid(&mut stdout)?,
+        Format::Json => {
+            let output = JsonOutput {
+                graph: &graph,
+                unresolved_features: unresolved_features
+                    .iter()
+                    .map(|(spec, feature)| JsonUnresolvedFeature { spec, feature })
+                    .collect(),
+            };
+            serde_json::to_writer_pretty(&mut stdout, &output)?;
+            writeln!(stdout)?;
+        }
+        Format::CyclonedxJson => cyclonedx(&graph, &licenses).write_json(&mut stdout)?,
+        Format::CyclonedxXml => cyclonedx(&graph, &licenses).write_xml(&mut stdout)?,
+        Format::Spdx => spdx(&graph, &licenses).write_tag_value(&mut stdout)?,
+        Format::SpdxJson => spdx(&graph, &licenses).write_json(&mut stdout)?,
+    }
+    Ok(unresolved_features.is_empty())
+}
+
+/// Resolve and print every matching release of the requested crates,
+/// returning whether every release and feature could be resolved.
+fn write_versions(cli: &Cli, resolver: &mut Resolver<'_>) -> anyhow::Result<bool> {
+    if !matches!(cli.format, Format::List | Format::Tree | Format::Duplicates) {
+        anyhow::bail!("--all-versions only supports the list, tree and duplicates formats");
+    }
+    let mut stdout = io::stdout().lock();
+    let mut resolved = true;
+    let mut first = true;
+    for spec in &cli.crates {
+        let (name, _) = parse_spec(spec);
+        for (version, result) in resolver.resolve_versions(&request(cli, spec))? {
+            let (graph, unresolved_features) = match result {
+                Ok(resolution) => resolution,
+                Err(e) => {
+                    eprintln!("warning: {name}@{version}: couldn't resolve dependencies: {e:#}");
+                    resolved = false;
+                    continue;
+                }
+            };
+            for err in unresolved_features {
+                eprintln!(
+                    "warning: {name}@{version}: couldn't resolve dependencies with feature '{}': {:#}",
+                    err.name, err.error
+                );
+                resolved = false;
+            }
+
+            if !first {
+                writeln!(stdout)?;
+            }
+            first = false;
+            match cli.format {
+                Format::Tree => tree(cli, &graph)?.write(&mut stdout)?,
+                Format::Duplicates => {
+                    writeln!(stdout, "{name} v{version}")?;
+                    write_duplicates(&mut stdout, &graph)?;
+                }
+                _ => {
+                    writeln!(stdout, "{name} v{version}")?;
+                    let roots = graph.roots().collect::<Vec<_>>();
+                    for package in graph.packages().filter(|p| !roots.contains(p)) {
+                        writeln!(stdout, "    {} {}", package.name, package.version)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+fn builder(cli: &Cli) -> ResolverBuilder {
+    let mut builder = Resolver::builder();
+    if let Some(registry) = &cli.registry {
+        builder = builder.registry(registry);
+    }
+    if let Some(index) = &cli.index {
+        builder = builder.index_url(index);
+    }
+    if let Some(path) = &cli.local_registry {
+        builder = builder.local_registry(path);
+    }
+    for target in &cli.target {
+        builder = builder.target(target);
+    }
+    for cfg in &cli.cfg {
+        builder = builder.cfg(cfg);
+    }
+    if let Some(host) = &cli.host {
+        builder = builder.host(host);
+    }
+    if let Some(path) = &cli.lockfile {
+        builder = builder.lockfile(path);
+    }
+    builder.version_strategy(match cli.versions {
+        Versions::Maximum => VersionStrategy::Maximum,
+        Versions::Minimal => VersionStrategy::Minimal,
+        Versions::DirectMinimal => VersionStrategy::DirectMinimal,
+    })
+}
+
+fn tree<'a>(cli: &Cli, graph: &'a DependencyGraph) -> anyhow::Result<Tree<'a>> {
+    let mut tree = Tree::new(graph);
+    if let Some(depth) = cli.depth {
+        tree = tree.depth(depth);
+    }
+    if let Some(spec) = &cli.invert {
+        tree = tree.invert(find_package(graph, spec)?);
+    }
+    if cli.duplicates {
+        tree = tree.duplicates();
+    }
+    Ok(tree)
+}
+
+/// Find the package in the graph matching a `name` or `name@version` spec.
+fn find_package<'a>(graph: &'a DependencyGraph, spec: &str) -> anyhow::Result<&'a Package> {
+    let (name, version) = parse_spec(spec);
+    let matches = graph
+        .packages()
+        .filter(|p| p.name == name && version.map_or(true, |v| p.version == v))
+        .collect::<Vec<_>>();
+    match matches[..] {
+        [package] => Ok(package),
+        [] => anyhow::bail!("package `{spec}` is not in the dependency graph"),
+        _ => anyhow::bail!(
+            "package `{spec}` is ambiguous, specify one of: {}",
+            matches
+                .iter()
+                .map(|p| format!("{}@{}", p.name, p.version))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn diagram<'a>(cli: &Cli, graph: &'a DependencyGraph) -> Diagram<'a> {
+    let mut diagram = Diagram::new(graph);
+    if cli.color_kinds {
+        diagram = diagram.color_kinds();
+    }
+    if cli.cluster_versions {
+        diagram = diagram.cluster_versions();
+    }
+    if cli.edge_features {
+        diagram = diagram.edge_features();
+    }
+    diagram
+}
+
+/// Get the licenses of the packages in the graph if the format includes
+/// them. Failing to get them is only a warning, since the output is still
+/// useful without them.
+fn licenses(
+    cli: &Cli,
+    resolver: &mut Resolver<'_>,
+    graph: &DependencyGraph,
+) -> BTreeMap<Package, String> {
+    let needed = matches!(
+        cli.format,
+        Format::CyclonedxJson | Format::CyclonedxXml | Format::Spdx | Format::SpdxJson
+    );
+    if !needed || cli.no_licenses {
+        return BTreeMap::new();
+    }
+    resolver.licenses(graph).unwrap_or_else(|e| {
+        eprintln!("warning: couldn't get the licenses of the crates: {e:#}");
+        BTreeMap::new()
+    })
+}
+
+fn cyclonedx<'a>(
+    graph: &'a DependencyGraph,
+    licenses: &BTreeMap<Package, String>,
+) -> CycloneDx<'a> {
+    licenses
+        .iter()
+        .fold(CycloneDx::new(graph), |bom, (package, license)| {
+            bom.license(package, license)
+        })
+}
+
+fn spdx<'a>(graph: &'a DependencyGraph, licenses: &BTreeMap<Package, String>) -> Spdx<'a> {
+    licenses
+        .iter()
+        .fold(Spdx::new(graph), |doc, (package, license)| {
+            doc.license(package, license)
+        })
+}
+
+fn request(cli: &Cli, spec: &str) -> ResolveRequest {
+    let (name, version) = parse_spec(spec);
+    let mut request = ResolveRequest::new(name, version);
+    if cli.no_default_features {
+        request = request.no_default_features();
+    }
+    if cli.all_features {
+        request = request.all_features();
+    } else if !cli.features.is_empty() {
+        request = request.features(features(cli));
+    }
+    if cli.no_build_deps {
+        request = request.no_build_dependencies();
+    }
+    if cli.dev_deps {
+        request = request.dev_dependencies();
+    }
+    request
+}
+
+fn workspace_request(cli: &Cli, manifest_path: &Path) -> WorkspaceRequest {
+    let mut request = WorkspaceRequest::new(manifest_path).features(features(cli));
+    if cli.no_default_features {
+        request = request.no_default_features();
+    }
+    if cli.all_features {
+        request = request.all_features();
+    }
+    if cli.no_build_deps {
+        request = request.no_build_dependencies();
+    }
+    if cli.dev_deps {
+        request = request.dev_dependencies();
+    }
+    if cli.no_lockfile {
+        request = request.no_lockfile();
+    }
+    request
+}
+
+/// Get the features from the command line, which may be separated by commas
+/// or spaces.
+fn features(cli: &Cli) -> impl Iterator<Item = &str> {
+    cli.features
+        .iter()
+        .flat_map(|f| f.split_whitespace())
+        .filter(|f| !f.is_empty())
+}
+
+/// Split a `name@version` spec into its name and version requirement.
+fn parse_spec(spec: &str) -> (&str, Option<&str>) {
+    match spec.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (spec, None),
+    }
+}
+
+fn write_list<W: Write>(w: &mut W, graph: &DependencyGraph) -> io::Result<()> {
+    for package in graph.packages() {
+        writeln!(w, "{} {}", package.name, package.version)?;
+    }
+    Ok(())
+}
+
+fn write_duplicates<W: Write>(w: &mut W, graph: &DependencyGraph) -> io::Result<()> {
+    for (name, versions) in graph.duplicates() {
+        writeln!(w, "{name}")?;
+        for (package, dependents) in versions {
+            writeln!(w, "    v{}", package.version)?;
+            for edge in dependents {
+                write!(
+                    w,
+                    "        {} v{} requires {}",
+                    edge.from.name, edge.from.version, edge.version_req
+                )?;
+                match edge.kind {
+                    DepKind::Normal => writeln!(w)?,
+                    DepKind::Build => writeln!(w, " (build)")?,
+                    DepKind::Development => writeln!(w, " (dev)")?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write each path from the roots to `target`, labeling each hop with its
+/// version requirement, kind, activating features and platform.
+fn write_paths<W: Write>(w: &mut W, graph: &DependencyGraph, target: &str) -> io::Result<()> {
+    for root in graph.roots() {
+        for path in graph.paths(root, target) {
+            write!(w, "{} v{}", root.name, root.version)?;
+            for edge in path {
+                write!(
+                    w,
+                    " -> {} v{} ({})",
+                    edge.to.name,
+                    edge.to.version,
+                    labels(edge)
+                )?;
+            }
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_diff<W: Write>(w: &mut W, diff: &GraphDiff) -> io::Result<()> {
+    for (heading, changes) in [("added", &diff.added), ("removed", &diff.removed)] {
+        if !changes.is_empty() {
+            writeln!(w, "{heading}:")?;
+        }
+        for change in changes {
+            writeln!(w, "    {} v{}", change.package.name, change.package.version)?;
+            write_dependents(w, &change.dependents)?;
+        }
+    }
+    if !diff.upgraded.is_empty() {
+        writeln!(w, "upgraded:")?;
+    }
+    for change in &diff.upgraded {
+        let versions = |versions: &[String]| {
+            let versions = versions.iter().map(|v| format!("v{v}")).collect::<Vec<_>>();
+            versions.join(", ")
+        };
+        writeln!(
+            w,
+            "    {} {} -> {}",
+            change.name,
+            versions(&change.from),
+            versions(&change.to)
+        )?;
+        write_dependents(w, &change.dependents)?;
+    }
+    if !diff.new_duplicates.is_empty() {
+        writeln!(w, "new duplicates:")?;
+    }
+    for (name, versions) in &diff.new_duplicates {
+        writeln!(w, "    {name}")?;
+        for change in versions {
+            writeln!(w, "        v{}", change.package.version)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_dependents<W: Write>(w: &mut W, dependents: &[Edge]) -> io::Result<()> {
+    for edge in dependents {
+        writeln!(
+            w,
+            "        required by {} v{} ({})",
+            edge.from.name,
+            edge.from.version,
+            labels(edge)
+        )?;
+    }
+    Ok(())
+}
+
+/// Describe an edge by its version requirement, kind, activating features and
+/// target.
+fn labels(edge: &Edge) -> String {
+    let mut labels = vec![edge.version_req.clone()];
+    match edge.kind {
+        DepKind::Normal => {}
+        DepKind::Build => labels.push("build".to_string()),
+        DepKind::Development => labels.push("dev".to_string()),
+    }
+    if !edge.features.is_empty() {
+        let features = edge.features.iter().cloned().collect::<Vec<_>>();
+        labels.push(format!("features: {}", features.join(", ")));
+    }
+    if let Some(target) = &edge.target {
+        labels.push(target.clone());
+    }
+    labels.join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn specs() {
+        assert_eq!(parse_spec("serde"), ("serde", None));
+        assert_eq!(parse_spec("serde@1.0.164"), ("serde", Some("1.0.164")));
+        assert_eq!(parse_spec("serde@^1"), ("serde", Some("^1")));
+    }
+
+    #[test]
+    fn feature_lists() {
+        let cli = Cli::parse_from([
+            "crate-deps",
+            "serde",
+            "-F",
+            "derive,rc",
+            "--features",
+            "std alloc",
+        ]);
+        assert_eq!(
+            request(&cli, "serde").features,
+            crate_deps::FeatureSelection::Features(
+                ["derive", "rc", "std", "alloc"].map(String::from).to_vec()
+            )
+        );
+
+        let cli = Cli::parse_from([
+            "crate-deps",
+            "serde",
+            "--all-features",
+            "--no-default-features",
+        ]);
+        let request = request(&cli, "serde");
+        assert!(!request.default_features);
+        assert_eq!(request.features, crate_deps::FeatureSelection::AllFeatures);
+    }
+}
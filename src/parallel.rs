@@ -0,0 +1,232 @@
+//! Resolving many requests on worker threads that share one Cargo home.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Poll;
+use std::thread;
+
+use cargo::core::dependency::DepKind as CargoDepKind;
+use cargo::core::registry::{PackageRegistry, Registry};
+use cargo::core::{Dependency, SourceId};
+use cargo::sources::source::QueryKind;
+use cargo::sources::IndexSummary;
+use cargo::util::cache_lock::CacheLockMode;
+use cargo::util::config::Config;
+use cargo::util::interning::InternedString;
+use cargo::CargoResult;
+
+use crate::{
+    request_dependencies, BatchResolution, DependencyGraph, ResolveRequest, ResolverBuilder,
+    Result, UnresolvedFeature,
+};
+
+/// The result of one request on a worker: its index in the batch, its graph,
+/// and the features that couldn't be resolved.
+type WorkerResult = (usize, DependencyGraph, Result<Vec<UnresolvedFeature>>);
+
+/// Resolve `requests` on `threads` worker threads. See
+/// [`ResolverBuilder::resolve_parallel`].
+pub(crate) fn resolve_parallel(
+    builder: &ResolverBuilder,
+    requests: &[ResolveRequest],
+    threads: usize,
+) -> Result<BatchResolution> {
+    {
+        let config = builder.config()?;
+        let mut resolver = builder.clone().build(&config)?;
+        let _lock = config.acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        warm_index(&mut *resolver.registry, resolver.source, requests)?;
+    }
+
+    let next = AtomicUsize::new(0);
+    let threads = threads.clamp(1, requests.len().max(1));
+    let mut results = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| scope.spawn(|| resolve_worker(builder, requests, &next)))
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("resolver worker panicked"))
+            .collect::<Result<Vec<_>>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    results.sort_by_key(|(i, _, _)| *i);
+
+    let mut batch = BatchResolution::default();
+    for (_, graph, result) in results {
+        batch.push(graph, result);
+    }
+    Ok(batch)
+}
+
+/// Read every registry index file that resolving `requests` may need, so
+/// workers find them in the index cache. The package cache must be locked.
+///
+/// This follows every dependency of every version that a dependency matches,
+/// which is more than any one resolve reads, but doesn't resolve anything.
+/// Queries are issued a round at a time, so a remote index can fetch the
+/// files of a round concurrently.
+fn warm_index(
+    registry: &mut dyn Registry,
+    source: SourceId,
+    requests: &[ResolveRequest],
+) -> Result<()> {
+    let mut seen = HashSet::new();
+    let mut pending = Vec::new();
+    // An invalid request fails again on its worker, which reports it.
+    for request in requests {
+        if let Ok(dep) = Dependency::parse(&request.package, request.version.as_deref(), source) {
+            enqueue(&mut seen, &mut pending, dep, true);
+        }
+    }
+    while !pending.is_empty() {
+        let mut next = Vec::new();
+        let mut waiting = false;
+        for (dep, root) in pending {
+            let mut deps = Vec::new();
+            let poll = registry.query(&dep, QueryKind::Exact, &mut |s: IndexSummary| {
+                deps.extend(s.as_summary().dependencies().iter().cloned())
+            });
+            match poll {
+                Poll::Ready(result) => result?,
+                Poll::Pending => {
+                    next.push((dep, root));
+                    waiting = true;
+                    continue;
+                }
+            }
+            // Only the requested packages' dev-dependencies are resolved.
+            for dep in deps {
+                if root || dep.kind() != CargoDepKind::Development {
+                    enqueue(&mut seen, &mut next, dep, false);
+                }
+            }
+        }
+        if waiting {
+            registry.block_until_ready()?;
+        }
+        pending = next;
+    }
+    Ok(())
+}
+
+/// Queue a dependency for [`warm_index`] unless it already was, with the same
+/// version requirement.
+fn enqueue(
+    seen: &mut HashSet<(InternedString, String, bool)>,
+    queue: &mut Vec<(Dependency, bool)>,
+    dep: Dependency,
+    root: bool,
+) {
+    if seen.insert((dep.package_name(), dep.version_req().to_string(), root)) {
+        queue.push((dep, root));
+    }
+}
+
+/// Resolve requests on a worker thread, taking the next unresolved request
+/// from `next` until there are none left.
+fn resolve_worker(
+    builder: &ResolverBuilder,
+    requests: &[ResolveRequest],
+    next: &AtomicUsize,
+) -> Result<Vec<WorkerResult>> {
+    let config = worker_config(builder)?;
+    // Keep the cache from being cleaned up while the worker reads it, without
+    // stopping other workers from reading it.
+    let _lock = config.acquire_package_cache_lock(CacheLockMode::Shared)?;
+    let mut registry = PackageRegistry::new(&config)?;
+    registry.lock_patches();
+    let registry = CacheLockedRegistry {
+        config: &config,
+        registry,
+    };
+    let mut resolver = builder
+        .clone()
+        .build_with_registry(&config, Box::new(registry))?;
+
+    let mut results = Vec::new();
+    loop {
+        let i = next.fetch_add(1, Ordering::Relaxed);
+        let Some(request) = requests.get(i) else {
+            break;
+        };
+        let mut graph = DependencyGraph::new();
+        let result = request_dependencies(
+            resolver.config,
+            resolver.source,
+            &mut *resolver.registry,
+            &resolver.versions,
+            &mut resolver.platforms,
+            request,
+            &mut graph,
+        );
+        results.push((i, graph, result));
+    }
+    Ok(results)
+}
+
+/// Load the Cargo config for a worker. It reads the registry index from the
+/// cache left by [`warm_index`] instead of updating it, and is quiet, since
+/// workers would otherwise report waiting for each other's locks.
+fn worker_config(builder: &ResolverBuilder) -> Result<Config> {
+    let mut config = builder.config()?;
+    // `-Z no-index-update` is unstable. `--offline` would also skip the
+    // update, but prefers versions that happen to be downloaded.
+    config.nightly_features_allowed = true;
+    config.configure(
+        0,
+        true,
+        None,
+        false,
+        false,
+        false,
+        &None,
+        &["no-index-update".to_string()],
+        &[],
+    )?;
+    Ok(config)
+}
+
+/// A registry that holds the package cache lock while it's queried.
+///
+/// Cargo asserts that the lock is held whenever a remote registry index is
+/// read, even from its cache, but workers only need it for that long.
+struct CacheLockedRegistry<'cfg> {
+    config: &'cfg Config,
+    registry: PackageRegistry<'cfg>,
+}
+
+impl Registry for CacheLockedRegistry<'_> {
+    fn query(
+        &mut self,
+        dep: &Dependency,
+        kind: QueryKind,
+        f: &mut dyn FnMut(IndexSummary),
+    ) -> Poll<CargoResult<()>> {
+        let _lock = match self
+            .config
+            .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)
+        {
+            Ok(lock) => lock,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        self.registry.query(dep, kind, f)
+    }
+
+    fn describe_source(&self, source: SourceId) -> String {
+        self.registry.describe_source(source)
+    }
+
+    fn is_replaced(&self, source: SourceId) -> bool {
+        self.registry.is_replaced(source)
+    }
+
+    fn block_until_ready(&mut self) -> CargoResult<()> {
+        let _lock = self
+            .config
+            .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        self.registry.block_until_ready()
+    }
+}
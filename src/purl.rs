@@ -0,0 +1,74 @@
+//! Package URLs (purls) of resolved packages.
+
+use crate::ResolvedPackage;
+
+/// Get the package URL of a package, e.g. `pkg:cargo/serde@1.0.197`.
+/// Packages from registries other than crates.io have a `repository_url`
+/// qualifier: the index URL, without the protocol prefix Cargo adds to it,
+/// so a registry has the same purl whether it's used through git or sparse.
+pub(crate) fn purl(package: &ResolvedPackage) -> String {
+    let mut purl = format!(
+        "pkg:cargo/{}@{}",
+        package.package.name,
+        percent_encode(&package.package.version)
+    );
+    if !package.is_crates_io() {
+        let url = ["registry+", "sparse+"]
+            .iter()
+            .find_map(|prefix| package.source.strip_prefix(prefix))
+            .unwrap_or(&package.source);
+        purl.push_str("?repository_url=");
+        purl.push_str(&percent_encode(url));
+    }
+    purl
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' | b':' | b'/' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{package, resolved};
+    use crate::CRATES_IO_SOURCES;
+
+    #[test]
+    fn purls() {
+        let purls = [
+            resolved(&package("root", "1.0.0"), CRATES_IO_SOURCES[0], &[]),
+            resolved(&package("a", "1.0.0+build.1"), CRATES_IO_SOURCES[1], &[]),
+            resolved(
+                &package("b", "0.1.0"),
+                "registry+https://example.com/index/",
+                &[],
+            ),
+            resolved(
+                &package("b", "0.1.0"),
+                "sparse+https://example.com/index/",
+                &[],
+            ),
+        ]
+        .iter()
+        .map(purl)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            purls,
+            [
+                "pkg:cargo/root@1.0.0",
+                "pkg:cargo/a@1.0.0%2Bbuild.1",
+                "pkg:cargo/b@0.1.0?repository_url=https://example.com/index/",
+                "pkg:cargo/b@0.1.0?repository_url=https://example.com/index/",
+            ]
+        );
+    }
+}
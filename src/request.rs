@@ -0,0 +1,163 @@
+//! Options for resolving a single package or a workspace.
+
+use std::path::PathBuf;
+
+/// The features of the requested package to enable during resolution.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub enum FeatureSelection {
+    /// Enable each feature that may activate an optional dependency on its
+    /// own, and merge the results. This yields the union of the dependencies
+    /// of every feature, even if some features conflict with each other.
+    #[default]
+    EachFeature,
+    /// Enable exactly the listed features.
+    Features(Vec<String>),
+    /// Enable every feature at once.
+    AllFeatures,
+}
+
+/// A request to resolve the dependencies of a single package.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResolveRequest {
+    pub package: String,
+    /// The version requirement for the package, or `None` for the latest
+    /// version.
+    pub version: Option<String>,
+    /// Whether to enable the package's `default` feature.
+    pub default_features: bool,
+    pub features: FeatureSelection,
+    /// Whether to include build dependencies.
+    pub build_dependencies: bool,
+    /// Whether to include the package's own dev-dependencies. Dev-dependencies
+    /// of other packages are never included.
+    pub dev_dependencies: bool,
+}
+
+impl ResolveRequest {
+    /// Create a request for the union of the dependencies of every feature,
+    /// with the default features enabled. Build dependencies are included,
+    /// dev-dependencies are not.
+    pub fn new(package: &str, version: Option<&str>) -> Self {
+        Self {
+            package: package.to_string(),
+            version: version.map(str::to_string),
+            default_features: true,
+            features: FeatureSelection::default(),
+            build_dependencies: true,
+            dev_dependencies: false,
+        }
+    }
+
+    /// Disable the package's default features.
+    pub fn no_default_features(mut self) -> Self {
+        self.default_features = false;
+        self
+    }
+
+    /// Enable exactly the listed features.
+    pub fn features<I, S>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.features = FeatureSelection::Features(features.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Enable every feature at once.
+    pub fn all_features(mut self) -> Self {
+        self.features = FeatureSelection::AllFeatures;
+        self
+    }
+
+    /// Leave out build dependencies.
+    pub fn no_build_dependencies(mut self) -> Self {
+        self.build_dependencies = false;
+        self
+    }
+
+    /// Include the package's own dev-dependencies.
+    pub fn dev_dependencies(mut self) -> Self {
+        self.dev_dependencies = true;
+        self
+    }
+}
+
+/// A request to resolve the members of a local workspace, or a single
+/// package's manifest.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WorkspaceRequest {
+    /// The path to a `Cargo.toml`, or the directory containing it.
+    pub manifest_path: PathBuf,
+    /// Whether to enable the members' `default` features.
+    pub default_features: bool,
+    /// The features to enable, like `--features` for Cargo. Features of a
+    /// specific member are given as `member/feature`.
+    pub features: Vec<String>,
+    /// Whether to enable every feature of every member.
+    pub all_features: bool,
+    /// Whether to include build dependencies.
+    pub build_dependencies: bool,
+    /// Whether to include the members' dev-dependencies.
+    pub dev_dependencies: bool,
+    /// Whether to prefer the versions pinned by the workspace's `Cargo.lock`.
+    pub lockfile: bool,
+}
+
+impl WorkspaceRequest {
+    /// Create a request for the members with their default features enabled.
+    /// Build dependencies are included, dev-dependencies are not, and the
+    /// workspace's `Cargo.lock` is honored if it has one.
+    pub fn new(manifest_path: impl Into<PathBuf>) -> Self {
+        Self {
+            manifest_path: manifest_path.into(),
+            default_features: true,
+            features: Vec::new(),
+            all_features: false,
+            build_dependencies: true,
+            dev_dependencies: false,
+            lockfile: true,
+        }
+    }
+
+    /// Disable the members' default features.
+    pub fn no_default_features(mut self) -> Self {
+        self.default_features = false;
+        self
+    }
+
+    /// Enable the listed features.
+    pub fn features<I, S>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.features = features.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Enable every feature of every member.
+    pub fn all_features(mut self) -> Self {
+        self.all_features = true;
+        self
+    }
+
+    /// Leave out build dependencies.
+    pub fn no_build_dependencies(mut self) -> Self {
+        self.build_dependencies = false;
+        self
+    }
+
+    /// Include the members' dev-dependencies.
+    pub fn dev_dependencies(mut self) -> Self {
+        self.dev_dependencies = true;
+        self
+    }
+
+    /// Ignore the workspace's `Cargo.lock`, resolving the newest compatible
+    /// versions like `cargo update`.
+    pub fn no_lockfile(mut self) -> Self {
+        self.lockfile = false;
+        self
+    }
+}
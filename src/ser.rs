@@ -0,0 +1,29 @@
+//! Serialization helpers for the `serde` feature.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use serde::{Serialize, Serializer};
+
+use crate::{Error, Package};
+
+/// Serialize a set of packages in sorted order, so output is stable.
+pub(crate) fn sorted<S: Serializer>(set: &HashSet<Package>, s: S) -> Result<S::Ok, S::Error> {
+    set.iter().collect::<BTreeSet<_>>().serialize(s)
+}
+
+/// Serialize a map of package sets with each set in sorted order.
+pub(crate) fn sorted_values<S: Serializer>(
+    map: &BTreeMap<String, HashSet<Package>>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter()
+        .map(|(k, v)| (k, v.iter().collect::<BTreeSet<_>>()))
+        .collect::<BTreeMap<_, _>>()
+        .serialize(s)
+}
+
+/// Serialize an error as its message, including the messages of the errors
+/// that caused it.
+pub(crate) fn error<S: Serializer>(error: &Error, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(&format_args!("{error:#}"))
+}
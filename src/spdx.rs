@@ -0,0 +1,426 @@
+//! SPDX 2.3 documents.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::purl::purl;
+use crate::{DependencyGraph, Package, ResolvedPackage};
+
+const NOASSERTION: &str = "NOASSERTION";
+
+/// An SPDX 2.3 document for a dependency graph.
+///
+/// Every package in the graph becomes an SPDX package with its download
+/// location, checksum and purl. The document `DESCRIBES` the roots, and each
+/// package `DEPENDS_ON` its direct dependencies.
+///
+/// The declared license is `NOASSERTION` unless it's provided with
+/// [`license`](Self::license), e.g. from
+/// [`Resolver::licenses`](crate::Resolver::licenses).
+#[derive(Clone, Debug)]
+pub struct Spdx<'a> {
+    graph: &'a DependencyGraph,
+    licenses: BTreeMap<Package, String>,
+    namespace: Option<String>,
+    created: SystemTime,
+}
+
+/// The fields of a package in the document.
+struct SpdxPackage<'a> {
+    id: String,
+    package: &'a ResolvedPackage,
+    download_location: String,
+    license: &'a str,
+    purl: String,
+}
+
+impl<'a> Spdx<'a> {
+    /// Create a document, created now.
+    pub fn new(graph: &'a DependencyGraph) -> Self {
+        Self {
+            graph,
+            licenses: BTreeMap::new(),
+            namespace: None,
+            created: SystemTime::now(),
+        }
+    }
+
+    /// Set the declared license of a package, as an SPDX license expression
+    /// such as `MIT OR Apache-2.0`.
+    pub fn license(mut self, package: &Package, expression: &str) -> Self {
+        self.licenses
+            .insert(package.clone(), expression.to_string());
+        self
+    }
+
+    /// Set the document namespace. By default, it's a URI under
+    /// `https://spdx.org/spdxdocs/` derived from the graph.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Set the time the document was created.
+    pub fn created(mut self, created: SystemTime) -> Self {
+        self.created = created;
+        self
+    }
+
+    /// Write the document in the tag-value format.
+    pub fn write_tag_value<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "SPDXVersion: SPDX-2.3")?;
+        writeln!(w, "DataLicense: CC0-1.0")?;
+        writeln!(w, "SPDXID: SPDXRef-DOCUMENT")?;
+        writeln!(w, "DocumentName: {}", self.name())?;
+        writeln!(w, "DocumentNamespace: {}", self.document_namespace())?;
+        writeln!(
+            w,
+            "Creator: Tool: {}-{}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )?;
+        writeln!(w, "Created: {}", timestamp(self.created))?;
+
+        for package in self.packages() {
+            writeln!(w)?;
+            writeln!(w, "PackageName: {}", package.package.package.name)?;
+            writeln!(w, "SPDXID: {}", package.id)?;
+            writeln!(w, "PackageVersion: {}", package.package.package.version)?;
+            writeln!(w, "PackageDownloadLocation: {}", package.download_location)?;
+            writeln!(w, "FilesAnalyzed: false")?;
+            if let Some(checksum) = &package.package.checksum {
+                writeln!(w, "PackageChecksum: SHA256: {checksum}")?;
+            }
+            writeln!(w, "PackageLicenseConcluded: {NOASSERTION}")?;
+            writeln!(w, "PackageLicenseDeclared: {}", package.license)?;
+            writeln!(w, "PackageCopyrightText: {NOASSERTION}")?;
+            writeln!(w, "ExternalRef: PACKAGE-MANAGER purl {}", package.purl)?;
+            writeln!(w, "PrimaryPackagePurpose: LIBRARY")?;
+        }
+
+        writeln!(w)?;
+        for (from, kind, to) in self.relationships() {
+            writeln!(w, "Relationship: {from} {kind} {to}")?;
+        }
+        Ok(())
+    }
+
+    /// Write the document in the JSON format.
+    #[cfg(feature = "serde")]
+    pub fn write_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        use serde_json::json;
+
+        let packages = self
+            .packages()
+            .into_iter()
+            .map(|package| {
+                let mut json = json!({
+                    "SPDXID": package.id,
+                    "name": package.package.package.name,
+                    "versionInfo": package.package.package.version,
+                    "downloadLocation": package.download_location,
+                    "filesAnalyzed": false,
+                    "licenseConcluded": NOASSERTION,
+                    "licenseDeclared": package.license,
+                    "copyrightText": NOASSERTION,
+                    "externalRefs": [{
+                        "referenceCategory": "PACKAGE-MANAGER",
+                        "referenceType": "purl",
+                        "referenceLocator": package.purl,
+                    }],
+                    "primaryPackagePurpose": "LIBRARY",
+                });
+                if let Some(checksum) = &package.package.checksum {
+                    json["checksums"] =
+                        json!([{ "algorithm": "SHA256", "checksumValue": checksum }]);
+                }
+                json
+            })
+            .collect::<Vec<_>>();
+        let relationships = self
+            .relationships()
+            .into_iter()
+            .map(|(from, kind, to)| {
+                json!({
+                    "spdxElementId": from,
+                    "relationshipType": kind,
+                    "relatedSpdxElement": to,
+                })
+            })
+            .collect::<Vec<_>>();
+        let document = json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": self.name(),
+            "documentNamespace": self.document_namespace(),
+            "creationInfo": {
+                "creators": [format!("Tool: {}-{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))],
+                "created": timestamp(self.created),
+            },
+            "packages": packages,
+            "relationships": relationships,
+        });
+        serde_json::to_writer_pretty(&mut *w, &document)?;
+        writeln!(w)
+    }
+
+    /// Get the document name, listing the roots.
+    fn name(&self) -> String {
+        self.graph
+            .roots()
+            .map(|root| format!("{}-{}", root.name, root.version))
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+
+    fn document_namespace(&self) -> String {
+        if let Some(namespace) = &self.namespace {
+            return namespace.clone();
+        }
+        let mut hasher = DefaultHasher::new();
+        for package in self.graph.packages() {
+            package.hash(&mut hasher);
+        }
+        format!(
+            "https://spdx.org/spdxdocs/{}-{:016x}",
+            self.name(),
+            hasher.finish()
+        )
+    }
+
+    fn packages(&self) -> Vec<SpdxPackage<'_>> {
+        let mut ids = self.ids();
+        self.graph
+            .resolved_packages()
+            .map(|package| SpdxPackage {
+                id: ids.remove(&package.package).unwrap(),
+                package,
+                download_location: download_location(package),
+                license: self
+                    .licenses
+                    .get(&package.package)
+                    .map_or(NOASSERTION, |l| l.as_str()),
+                purl: purl(package),
+            })
+            .collect()
+    }
+
+    /// Get the `DESCRIBES` relationships of the document and the
+    /// `DEPENDS_ON` relationships between packages.
+    fn relationships(&self) -> Vec<(String, &'static str, String)> {
+        let ids = self.ids();
+        let describes = self.graph.roots().map(|root| {
+            (
+                "SPDXRef-DOCUMENT".to_string(),
+                "DESCRIBES",
+                ids[root].clone(),
+            )
+        });
+        let depends_on = self
+            .graph
+            .edges()
+            .map(|edge| (ids[&edge.from].clone(), "DEPENDS_ON", ids[&edge.to].clone()))
+            .collect::<BTreeSet<_>>();
+        describes.chain(depends_on).collect()
+    }
+
+    /// Get the SPDX identifier of each package. Identifiers may only contain
+    /// letters, digits, `.` and `-`, so other characters in the name and
+    /// version are replaced, and each identifier starts with the package's
+    /// index to keep it unique, e.g. `SPDXRef-Package-3-a-b-1.0.0-build.1`
+    /// for `a_b 1.0.0+build.1`.
+    fn ids(&self) -> BTreeMap<&'a Package, String> {
+        self.graph
+            .packages()
+            .enumerate()
+            .map(|(i, package)| {
+                let id = format!("SPDXRef-Package-{i}-{}-{}", package.name, package.version);
+                let id = id.replace(
+                    |c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-',
+                    "-",
+                );
+                (package, id)
+            })
+            .collect()
+    }
+}
+
+/// Get the download URL of a package from crates.io. The download URLs of
+/// other registries aren't known.
+fn download_location(package: &ResolvedPackage) -> String {
+    if package.is_crates_io() {
+        format!(
+            "https://crates.io/api/v1/crates/{}/{}/download",
+            package.package.name, package.package.version
+        )
+    } else {
+        NOASSERTION.to_string()
+    }
+}
+
+/// Format a time as an SPDX timestamp, e.g. `2024-05-01T12:00:00Z`.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch to a civil date, from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::testing::{edge, package, resolved};
+    use crate::{DepKind, CRATES_IO_SOURCES};
+
+    fn graph() -> (DependencyGraph, ResolvedPackage) {
+        let root = resolved(&package("root", "1.0.0"), CRATES_IO_SOURCES[0], &[]);
+        let a = resolved(
+            &package("a_b", "1.0.0+build.1"),
+            "sparse+https://example.com/index/",
+            &[],
+        );
+        let mut graph = DependencyGraph::new();
+        graph.add_root(root.clone());
+        graph.add_package(a.clone());
+        graph.add_edge(edge(&root.package, &a.package, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root.package, &a.package, DepKind::Build, &[]));
+        (graph, root)
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(1709251199)),
+            "2024-02-29T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn tag_value() {
+        let (graph, root) = graph();
+        let mut doc = Vec::new();
+        Spdx::new(&graph)
+            .license(&root.package, "MIT OR Apache-2.0")
+            .namespace("https://example.com/spdx/root")
+            .created(UNIX_EPOCH)
+            .write_tag_value(&mut doc)
+            .unwrap();
+        let doc = String::from_utf8(doc).unwrap();
+
+        assert!(doc.starts_with(concat!(
+            "SPDXVersion: SPDX-2.3\n",
+            "DataLicense: CC0-1.0\n",
+            "SPDXID: SPDXRef-DOCUMENT\n",
+            "DocumentName: root-1.0.0\n",
+            "DocumentNamespace: https://example.com/spdx/root\n",
+        )));
+        assert!(doc.contains("Created: 1970-01-01T00:00:00Z\n"));
+        assert!(doc.contains(concat!(
+            "PackageName: a_b\n",
+            "SPDXID: SPDXRef-Package-0-a-b-1.0.0-build.1\n",
+            "PackageVersion: 1.0.0+build.1\n",
+            "PackageDownloadLocation: NOASSERTION\n",
+        )));
+        assert!(doc.contains(concat!(
+            "PackageName: root\n",
+            "SPDXID: SPDXRef-Package-1-root-1.0.0\n",
+            "PackageVersion: 1.0.0\n",
+            "PackageDownloadLocation: https://crates.io/api/v1/crates/root/1.0.0/download\n",
+            "FilesAnalyzed: false\n",
+            "PackageChecksum: SHA256: 000000000000000000000000000000000000000000000000000000000000root\n",
+            "PackageLicenseConcluded: NOASSERTION\n",
+            "PackageLicenseDeclared: MIT OR Apache-2.0\n",
+            "PackageCopyrightText: NOASSERTION\n",
+            "ExternalRef: PACKAGE-MANAGER purl pkg:cargo/root@1.0.0\n",
+        )));
+        assert!(doc.ends_with(concat!(
+            "\n\n",
+            "Relationship: SPDXRef-DOCUMENT DESCRIBES SPDXRef-Package-1-root-1.0.0\n",
+            "Relationship: SPDXRef-Package-1-root-1.0.0 DEPENDS_ON SPDXRef-Package-0-a-b-1.0.0-build.1\n",
+        )));
+    }
+
+    #[test]
+    fn unique_ids() {
+        let (mut graph, root) = graph();
+        for version in ["1.0.0+build.1", "1.0.0-build.1"] {
+            let x = resolved(&package("x", version), CRATES_IO_SOURCES[0], &[]);
+            graph.add_package(x.clone());
+            graph.add_edge(edge(&root.package, &x.package, DepKind::Normal, &[]));
+        }
+        let spdx = Spdx::new(&graph);
+        let ids = spdx.ids().into_values().collect::<BTreeSet<_>>();
+        assert_eq!(ids.len(), 4);
+        assert_eq!(
+            spdx.packages()
+                .iter()
+                .map(|p| p.id.as_str())
+                .collect::<BTreeSet<_>>(),
+            ids.iter().map(String::as_str).collect()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        let (graph, _) = graph();
+        let mut doc = Vec::new();
+        Spdx::new(&graph)
+            .created(UNIX_EPOCH)
+            .write_json(&mut doc)
+            .unwrap();
+        let doc = serde_json::from_slice::<serde_json::Value>(&doc).unwrap();
+
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        assert!(doc["documentNamespace"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://spdx.org/spdxdocs/root-1.0.0-"));
+        assert_eq!(doc["creationInfo"]["created"], "1970-01-01T00:00:00Z");
+        assert_eq!(
+            doc["packages"][0],
+            serde_json::json!({
+                "SPDXID": "SPDXRef-Package-0-a-b-1.0.0-build.1",
+                "name": "a_b",
+                "versionInfo": "1.0.0+build.1",
+                "downloadLocation": "NOASSERTION",
+                "filesAnalyzed": false,
+                "checksums": [{ "algorithm": "SHA256", "checksumValue": format!("{:0>64}", "a_b") }],
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": "NOASSERTION",
+                "copyrightText": "NOASSERTION",
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": "pkg:cargo/a_b@1.0.0%2Bbuild.1?repository_url=https://example.com/index/",
+                }],
+                "primaryPackagePurpose": "LIBRARY",
+            })
+        );
+        assert_eq!(doc["relationships"].as_array().unwrap().len(), 2);
+    }
+}
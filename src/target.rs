@@ -0,0 +1,187 @@
+//! Target platforms for evaluating platform-specific dependencies.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use cargo::core::{Dependency, PackageId};
+use cargo::util::cache_lock::CacheLockMode;
+use cargo::util::config::Config;
+use cargo_platform::{Cfg, Platform};
+
+use crate::{download, Error, Result};
+
+const BUILTIN_TARGET_CFGS: &str = include_str!("target_cfgs.txt");
+
+/// The target triple this crate was built for, the default host.
+pub(crate) const DEFAULT_HOST: &str = env!("CRATE_DEPS_HOST");
+
+/// Get the target triples that have built-in cfg tables.
+pub fn builtin_targets() -> impl Iterator<Item = &'static str> {
+    BUILTIN_TARGET_CFGS
+        .lines()
+        .filter_map(|line| line.strip_prefix('[')?.strip_suffix(']'))
+}
+
+/// A target platform that platform-specific dependencies are evaluated
+/// against.
+#[derive(Clone, Debug)]
+pub(crate) struct Target {
+    triple: String,
+    cfgs: Vec<Cfg>,
+}
+
+impl Target {
+    /// Look up a target in the built-in cfg tables, adding the `extra_cfgs`.
+    pub(crate) fn new(triple: &str, extra_cfgs: &[Cfg]) -> Result<Self> {
+        let mut cfgs = builtin_cfgs(triple)
+            .ok_or_else(|| Error::UnknownTarget(triple.to_string()))?
+            .map(|cfg| parse_cfg(cfg).expect("invalid built-in target cfg"))
+            .collect::<Vec<_>>();
+        cfgs.extend(extra_cfgs.iter().cloned());
+        Ok(Self {
+            triple: triple.to_string(),
+            cfgs,
+        })
+    }
+
+    /// Check whether a dependency restricted to `platform` applies to this
+    /// target.
+    pub(crate) fn matches(&self, platform: &Platform) -> bool {
+        platform.matches(&self.triple, &self.cfgs)
+    }
+}
+
+/// The platforms that platform-specific dependencies are evaluated against,
+/// like `cargo tree --target`: the targets, and the host for build
+/// dependencies and proc-macros.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Platforms {
+    targets: Vec<Target>,
+    /// Only set along with `targets`, since without them every dependency
+    /// applies.
+    host: Option<Target>,
+    /// Whether packages are proc-macros, which the registry index doesn't
+    /// record.
+    proc_macros: HashMap<PackageId, bool>,
+}
+
+impl Platforms {
+    /// Evaluate dependencies against the `targets`, or against `host` for
+    /// those built for it. Use the default for every platform instead.
+    pub(crate) fn new(targets: Vec<Target>, host: Target) -> Self {
+        Self {
+            targets,
+            host: Some(host),
+            proc_macros: HashMap::new(),
+        }
+    }
+
+    /// Check whether `dep` applies to any of the targets or, if `on_host`,
+    /// to the host.
+    pub(crate) fn matches(&self, dep: &Dependency, on_host: bool) -> bool {
+        let (Some(platform), Some(host)) = (dep.platform(), &self.host) else {
+            return true;
+        };
+        if on_host {
+            host.matches(platform)
+        } else {
+            self.targets.iter().any(|t| t.matches(platform))
+        }
+    }
+
+    /// Find out which of `pkg_ids` are proc-macros, if dependencies are
+    /// evaluated against the host at all. Only their manifests say so, so
+    /// packages that weren't seen before are downloaded, like `cargo tree`
+    /// does.
+    pub(crate) fn load_proc_macros(
+        &mut self,
+        config: &Config,
+        pkg_ids: impl IntoIterator<Item = PackageId>,
+    ) -> Result<()> {
+        if self.host.is_none() {
+            return Ok(());
+        }
+        let pkg_ids = pkg_ids
+            .into_iter()
+            .filter(|id| !self.proc_macros.contains_key(id))
+            .collect::<Vec<_>>();
+        if pkg_ids.is_empty() {
+            return Ok(());
+        }
+        let _lock = config.acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        let packages = download(config, &pkg_ids)?;
+        for package in packages.get_many(pkg_ids.iter().copied())? {
+            self.proc_macros
+                .insert(package.package_id(), package.proc_macro());
+        }
+        Ok(())
+    }
+
+    /// Check whether a package is a proc-macro, as loaded by
+    /// [`load_proc_macros`](Self::load_proc_macros).
+    pub(crate) fn is_proc_macro(&self, pkg_id: PackageId) -> bool {
+        self.proc_macros.get(&pkg_id).copied().unwrap_or(false)
+    }
+}
+
+/// Parse a cfg in the format printed by `rustc --print cfg`, e.g. `unix` or
+/// `target_os="linux"`.
+pub(crate) fn parse_cfg(cfg: &str) -> Result<Cfg> {
+    Cfg::from_str(cfg).map_err(|_| Error::InvalidCfg(cfg.to_string()))
+}
+
+fn builtin_cfgs(triple: &str) -> Option<impl Iterator<Item = &'static str>> {
+    let header = format!("[{triple}]");
+    let mut lines = BUILTIN_TARGET_CFGS.lines();
+    lines.find(|line| *line == header)?;
+    Some(lines.take_while(|line| !line.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(s: &str) -> Platform {
+        Platform::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn builtin_tables_parse() {
+        for triple in builtin_targets() {
+            assert!(Target::new(triple, &[]).is_ok(), "{triple}");
+        }
+        assert!(builtin_targets().any(|t| t == "x86_64-unknown-linux-gnu"));
+    }
+
+    #[test]
+    fn matches_platforms() {
+        let linux = Target::new("x86_64-unknown-linux-gnu", &[]).unwrap();
+        assert!(linux.matches(&platform("cfg(unix)")));
+        assert!(linux.matches(&platform(r#"cfg(target_os = "linux")"#)));
+        assert!(linux.matches(&platform("x86_64-unknown-linux-gnu")));
+        assert!(!linux.matches(&platform("cfg(windows)")));
+        assert!(!linux.matches(&platform("aarch64-unknown-linux-gnu")));
+        assert!(!linux.matches(&platform("cfg(tokio_unstable)")));
+
+        let wasm = Target::new("wasm32-unknown-unknown", &[]).unwrap();
+        assert!(wasm.matches(&platform(r#"cfg(target_arch = "wasm32")"#)));
+        assert!(!wasm.matches(&platform("cfg(unix)")));
+    }
+
+    #[test]
+    fn extra_cfgs() {
+        let cfgs = [parse_cfg("tokio_unstable").unwrap()];
+        let linux = Target::new("x86_64-unknown-linux-gnu", &cfgs).unwrap();
+        assert!(linux.matches(&platform("cfg(tokio_unstable)")));
+        assert!(linux.matches(&platform("cfg(all(unix, tokio_unstable))")));
+    }
+
+    #[test]
+    fn unknown_target() {
+        assert!(matches!(
+            Target::new("x86_64-unknown-nowhere", &[]),
+            Err(Error::UnknownTarget(_))
+        ));
+        assert!(matches!(parse_cfg("not a cfg"), Err(Error::InvalidCfg(_))));
+    }
+}
@@ -0,0 +1,490 @@
+//! Helpers for tests: packages and edges for graphs built by hand, and
+//! registries on disk or in memory.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::task::Poll;
+use std::thread;
+
+use cargo::core::dependency::DepKind as CargoDepKind;
+use cargo::core::registry::Registry;
+use cargo::core::summary::Summary;
+use cargo::core::{Dependency, PackageId, Shell, SourceId};
+use cargo::sources::source::QueryKind;
+use cargo::sources::IndexSummary;
+use cargo::util::config::Config;
+use cargo::util::interning::InternedString;
+use cargo::util::IntoUrl;
+use cargo::CargoResult;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use crate::{DepKind, Edge, Package, ResolvedPackage, Resolver, ResolverBuilder};
+
+/// The source of the packages in graphs built by hand.
+pub const EXAMPLE_SOURCE: &str = "registry+https://example.com/index";
+
+pub fn package(name: &str, version: &str) -> Package {
+    Package {
+        name: name.to_string(),
+        version: version.to_string(),
+    }
+}
+
+/// A resolved package from `source`, with a checksum derived from its name.
+pub fn resolved(package: &Package, source: &str, features: &[&str]) -> ResolvedPackage {
+    ResolvedPackage {
+        package: package.clone(),
+        source: source.to_string(),
+        checksum: Some(format!("{:0>64}", package.name)),
+        features: features.iter().map(|f| f.to_string()).collect(),
+        links: None,
+        rust_version: None,
+    }
+}
+
+/// An edge requiring the version of `to`, which is optional if it's activated
+/// by `features`.
+pub fn edge(from: &Package, to: &Package, kind: DepKind, features: &[&str]) -> Edge {
+    Edge {
+        from: from.clone(),
+        to: to.clone(),
+        version_req: format!("^{}", to.version),
+        kind,
+        optional: !features.is_empty(),
+        features: features.iter().map(|f| f.to_string()).collect(),
+        target: None,
+    }
+}
+
+/// A local registry (`cargo local-registry` layout) in a temporary directory.
+pub struct TestRegistry {
+    dir: TempDir,
+}
+
+impl TestRegistry {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("index")).unwrap();
+        Self { dir }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn index_path(&self) -> PathBuf {
+        self.dir.path().join("index")
+    }
+
+    /// Add a package to the registry index, and its `.crate` file to the
+    /// registry.
+    pub fn publish(&self, package: &TestPackage) {
+        let crate_file = format!("{}-{}.crate", package.name, package.version);
+        fs::write(self.path().join(crate_file), package.crate_file()).unwrap();
+
+        let path = self.index_path().join(index_file(&package.name));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        writeln!(file, "{}", package.to_index_line()).unwrap();
+    }
+
+    /// Write a Cargo config to `cargo_home` that replaces crates.io with this
+    /// registry.
+    pub fn replace_crates_io(&self, cargo_home: &Path) {
+        fs::write(
+            cargo_home.join("config.toml"),
+            format!(
+                "[source.crates-io]\nreplace-with = \"test\"\n\n\
+                 [source.test]\nlocal-registry = {:?}\n",
+                self.path()
+            ),
+        )
+        .unwrap();
+    }
+
+    /// Serve the registry index over HTTP as a sparse index, returning its
+    /// `sparse+http://` URL. The server runs until the test process exits.
+    pub fn serve_sparse(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        fs::write(
+            self.index_path().join("config.json"),
+            format!(r#"{{"dl":"{url}dl"}}"#),
+        )
+        .unwrap();
+
+        let root = self.index_path();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                serve_file(&root, stream);
+            }
+        });
+        format!("sparse+{url}")
+    }
+}
+
+/// A registry that keeps its packages in memory, for tests that shouldn't
+/// need a registry on disk or the network.
+#[derive(Clone)]
+pub struct MockRegistry {
+    source: SourceId,
+    summaries: Vec<Summary>,
+}
+
+impl MockRegistry {
+    pub fn new() -> Self {
+        let url = "https://example.com/index".into_url().unwrap();
+        Self {
+            source: SourceId::for_registry(&url).unwrap(),
+            summaries: Vec::new(),
+        }
+    }
+
+    /// Add a package to the registry. Its Rust version is left out.
+    pub fn publish(&mut self, package: &TestPackage) {
+        let deps = package
+            .deps
+            .iter()
+            .map(|d| d.to_dependency(self.source))
+            .collect();
+        let features = package
+            .features
+            .iter()
+            .map(|(name, values)| {
+                let values = values.iter().map(|v| InternedString::new(v)).collect();
+                (InternedString::new(name), values)
+            })
+            .collect();
+        let pkg_id = PackageId::try_new(&package.name, &package.version, self.source).unwrap();
+        let mut summary =
+            Summary::new(pkg_id, deps, &features, package.links.as_deref(), None).unwrap();
+        summary.set_checksum(package.checksum());
+        self.summaries.push(summary);
+    }
+
+    /// A config that doesn't print anything, created without reading any
+    /// configuration files. Resolvers only lock the package cache in
+    /// `cargo_home`.
+    pub fn config(cargo_home: &Path) -> Config {
+        let shell = Shell::from_write(Box::new(io::sink()));
+        Config::new(shell, cargo_home.to_path_buf(), cargo_home.to_path_buf())
+    }
+
+    /// Create a resolver from `builder` that queries this registry.
+    pub fn resolver(self, builder: ResolverBuilder, config: &Config) -> Resolver<'_> {
+        builder
+            .index_url(self.source.url().as_str())
+            .build_with_registry(config, Box::new(self))
+            .unwrap()
+    }
+}
+
+impl Registry for MockRegistry {
+    fn query(
+        &mut self,
+        dep: &Dependency,
+        _kind: QueryKind,
+        f: &mut dyn FnMut(IndexSummary),
+    ) -> Poll<CargoResult<()>> {
+        for summary in self.summaries.iter().filter(|s| dep.matches(s)) {
+            f(IndexSummary::Candidate(summary.clone()));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn describe_source(&self, source: SourceId) -> String {
+        source.to_string()
+    }
+
+    fn is_replaced(&self, _source: SourceId) -> bool {
+        false
+    }
+
+    fn block_until_ready(&mut self) -> CargoResult<()> {
+        Ok(())
+    }
+}
+
+fn serve_file(root: &Path, mut stream: TcpStream) {
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    if reader.read_line(&mut request).is_err() {
+        return;
+    }
+    loop {
+        let mut header = String::new();
+        match reader.read_line(&mut header) {
+            Ok(0) | Err(_) => return,
+            Ok(_) if header == "\r\n" => break,
+            Ok(_) => {}
+        }
+    }
+
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let response = match fs::read(root.join(path.trim_start_matches('/'))) {
+        Ok(body) => {
+            let mut response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .into_bytes();
+            response.extend(body);
+            response
+        }
+        Err(_) => {
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+        }
+    };
+    let _ = stream.write_all(&response);
+}
+
+/// A package to publish to a [`TestRegistry`].
+#[derive(Clone)]
+pub struct TestPackage {
+    name: String,
+    version: String,
+    deps: Vec<TestDep>,
+    features: BTreeMap<String, Vec<String>>,
+    links: Option<String>,
+    rust_version: Option<String>,
+    license: Option<String>,
+    proc_macro: bool,
+}
+
+impl TestPackage {
+    pub fn new(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            deps: Vec::new(),
+            features: BTreeMap::new(),
+            links: None,
+            rust_version: None,
+            license: None,
+            proc_macro: false,
+        }
+    }
+
+    pub fn dep(mut self, dep: TestDep) -> Self {
+        self.deps.push(dep);
+        self
+    }
+
+    pub fn feature(mut self, name: &str, values: &[&str]) -> Self {
+        self.features.insert(
+            name.to_string(),
+            values.iter().map(|v| v.to_string()).collect(),
+        );
+        self
+    }
+
+    pub fn links(mut self, links: &str) -> Self {
+        self.links = Some(links.to_string());
+        self
+    }
+
+    pub fn rust_version(mut self, rust_version: &str) -> Self {
+        self.rust_version = Some(rust_version.to_string());
+        self
+    }
+
+    /// Set the license in the package's manifest. It isn't in the index.
+    pub fn license(mut self, license: &str) -> Self {
+        self.license = Some(license.to_string());
+        self
+    }
+
+    /// Make the package's library a proc-macro. It isn't in the index.
+    pub fn proc_macro(mut self) -> Self {
+        self.proc_macro = true;
+        self
+    }
+
+    pub fn publish(self, registry: &TestRegistry) -> Self {
+        registry.publish(&self);
+        self
+    }
+
+    /// The checksum recorded in the index for this package: the SHA-256
+    /// digest of its `.crate` file.
+    pub fn checksum(&self) -> String {
+        let digest = Sha256::digest(self.crate_file());
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Build the package's `.crate` file: a gzipped tarball of a manifest
+    /// and an empty library. It's the same for every call, so the checksum
+    /// is stable.
+    fn crate_file(&self) -> Vec<u8> {
+        let mut manifest = format!(
+            "[package]\nname = {}\nversion = {}\n",
+            json_str(&self.name),
+            json_str(&self.version)
+        );
+        if let Some(license) = &self.license {
+            manifest.push_str(&format!("license = {}\n", json_str(license)));
+        }
+        if self.proc_macro {
+            manifest.push_str("\n[lib]\nproc-macro = true\n");
+        }
+
+        let dir = format!("{}-{}", self.name, self.version);
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, contents) in [("Cargo.toml", manifest.as_str()), ("src/lib.rs", "")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(0);
+            tar.append_data(&mut header, format!("{dir}/{path}"), contents.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn to_index_line(&self) -> String {
+        let deps = self
+            .deps
+            .iter()
+            .map(TestDep::to_json)
+            .collect::<Vec<_>>()
+            .join(",");
+        let features = self
+            .features
+            .iter()
+            .map(|(name, values)| format!("{}:{}", json_str(name), json_list(values)))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            r#"{{"name":{},"vers":{},"deps":[{}],"cksum":{},"features":{{{}}},"yanked":false,"links":{},"rust_version":{}}}"#,
+            json_str(&self.name),
+            json_str(&self.version),
+            deps,
+            json_str(&self.checksum()),
+            features,
+            json_opt(self.links.as_deref()),
+            json_opt(self.rust_version.as_deref()),
+        )
+    }
+}
+
+/// A dependency of a [`TestPackage`].
+#[derive(Clone)]
+pub struct TestDep {
+    name: String,
+    req: String,
+    kind: &'static str,
+    optional: bool,
+    default_features: bool,
+    features: Vec<String>,
+    target: Option<String>,
+}
+
+impl TestDep {
+    pub fn new(name: &str, req: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            req: req.to_string(),
+            kind: "normal",
+            optional: false,
+            default_features: true,
+            features: Vec::new(),
+            target: None,
+        }
+    }
+
+    pub fn build(mut self) -> Self {
+        self.kind = "build";
+        self
+    }
+
+    pub fn dev(mut self) -> Self {
+        self.kind = "dev";
+        self
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    pub fn features(mut self, features: &[&str]) -> Self {
+        self.features = features.iter().map(|f| f.to_string()).collect();
+        self
+    }
+
+    pub fn target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    fn to_dependency(&self, source: SourceId) -> Dependency {
+        let mut dep = Dependency::parse(self.name.as_str(), Some(&self.req), source).unwrap();
+        dep.set_kind(match self.kind {
+            "build" => CargoDepKind::Build,
+            "dev" => CargoDepKind::Development,
+            _ => CargoDepKind::Normal,
+        })
+        .set_optional(self.optional)
+        .set_default_features(self.default_features)
+        .set_features(self.features.iter().map(String::as_str))
+        .set_platform(self.target.as_ref().map(|t| t.parse().unwrap()));
+        dep
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            r#"{{"name":{},"req":{},"features":{},"optional":{},"default_features":{},"target":{},"kind":{}}}"#,
+            json_str(&self.name),
+            json_str(&self.req),
+            json_list(&self.features),
+            self.optional,
+            self.default_features,
+            json_opt(self.target.as_deref()),
+            json_str(self.kind),
+        )
+    }
+}
+
+/// Write a local package with a `Cargo.toml` and an empty library to `dir`.
+pub fn write_package(dir: &Path, manifest: &str) {
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    fs::write(dir.join("src/lib.rs"), "").unwrap();
+}
+
+fn index_file(name: &str) -> PathBuf {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => Path::new("1").join(&name),
+        2 => Path::new("2").join(&name),
+        3 => Path::new("3").join(&name[..1]).join(&name),
+        _ => Path::new(&name[..2]).join(&name[2..4]).join(&name),
+    }
+}
+
+fn json_str(s: &str) -> String {
+    format!("{s:?}")
+}
+
+fn json_opt(s: Option<&str>) -> String {
+    s.map(json_str).unwrap_or_else(|| "null".to_string())
+}
+
+fn json_list(values: &[String]) -> String {
+    let values = values
+        .iter()
+        .map(|v| json_str(v))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("[{values}]")
+}
@@ -0,0 +1,272 @@
+//! `cargo tree` style text output of dependency graphs.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{self, Write};
+
+use crate::{DepKind, DependencyGraph, Package};
+
+/// An indented tree of a dependency graph, formatted like `cargo tree`.
+///
+/// Each root is printed with its dependencies below it, build and
+/// dev-dependencies under `[build-dependencies]` and `[dev-dependencies]`
+/// headings. A package that has already been printed under a root isn't
+/// expanded again; it's marked with `(*)` instead.
+#[derive(Clone, Debug)]
+pub struct Tree<'a> {
+    graph: &'a DependencyGraph,
+    depth: Option<usize>,
+    invert: Option<Package>,
+    duplicates: bool,
+}
+
+impl<'a> Tree<'a> {
+    pub fn new(graph: &'a DependencyGraph) -> Self {
+        Self {
+            graph,
+            depth: None,
+            invert: None,
+            duplicates: false,
+        }
+    }
+
+    /// Only print packages up to `depth` levels below the roots.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Print the packages that depend on `package`, like `cargo tree
+    /// --invert`.
+    pub fn invert(mut self, package: &Package) -> Self {
+        self.invert = Some(package.clone());
+        self
+    }
+
+    /// Print the packages that depend on each crate that's in the graph with
+    /// more than one version, like `cargo tree --duplicates`. Overrides
+    /// [`invert`](Self::invert).
+    pub fn duplicates(mut self) -> Self {
+        self.duplicates = true;
+        self
+    }
+
+    /// Write the tree.
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let (roots, inverted) = if self.duplicates {
+            let duplicates = self.graph.duplicates();
+            (
+                duplicates
+                    .into_values()
+                    .flat_map(BTreeMap::into_keys)
+                    .collect(),
+                true,
+            )
+        } else if let Some(package) = &self.invert {
+            (vec![package], true)
+        } else {
+            (self.graph.roots().collect(), false)
+        };
+
+        for (i, root) in roots.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(w)?;
+            }
+            let mut printer = Printer {
+                graph: self.graph,
+                depth: self.depth,
+                inverted,
+                levels: Vec::new(),
+                visited: HashSet::new(),
+            };
+            printer.write_node(w, root)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of printing the tree of a single root.
+struct Printer<'a> {
+    graph: &'a DependencyGraph,
+    depth: Option<usize>,
+    inverted: bool,
+    /// Whether each level above the current node has more entries to print
+    /// after it.
+    levels: Vec<bool>,
+    visited: HashSet<&'a Package>,
+}
+
+impl<'a> Printer<'a> {
+    fn write_node<W: Write>(&mut self, w: &mut W, package: &'a Package) -> io::Result<()> {
+        if let Some((last, parents)) = self.levels.split_last() {
+            for &more in parents {
+                write!(w, "{}   ", if more { '│' } else { ' ' })?;
+            }
+            write!(w, "{}── ", if *last { '├' } else { '└' })?;
+        }
+        write!(w, "{} v{}", package.name, package.version)?;
+
+        let neighbors = self.neighbors(package);
+        let new = self.visited.insert(package);
+        if !new && !neighbors.is_empty() {
+            return writeln!(w, " (*)");
+        }
+        writeln!(w)?;
+        if !new {
+            return Ok(());
+        }
+
+        for (kind, packages) in neighbors {
+            self.write_kind(w, kind, packages)?;
+        }
+        Ok(())
+    }
+
+    fn write_kind<W: Write>(
+        &mut self,
+        w: &mut W,
+        kind: DepKind,
+        packages: BTreeSet<&'a Package>,
+    ) -> io::Result<()> {
+        if self.depth.is_some_and(|depth| self.levels.len() >= depth) {
+            return Ok(());
+        }
+        let heading = match kind {
+            DepKind::Normal => None,
+            DepKind::Build => Some("[build-dependencies]"),
+            DepKind::Development => Some("[dev-dependencies]"),
+        };
+        if let Some(heading) = heading {
+            for &more in &self.levels {
+                write!(w, "{}   ", if more { '│' } else { ' ' })?;
+            }
+            writeln!(w, "{heading}")?;
+        }
+        let mut packages = packages.into_iter().peekable();
+        while let Some(package) = packages.next() {
+            self.levels.push(packages.peek().is_some());
+            self.write_node(w, package)?;
+            self.levels.pop();
+        }
+        Ok(())
+    }
+
+    /// Get the dependencies of a package by kind, or its dependents if the
+    /// tree is inverted.
+    fn neighbors(&self, package: &'a Package) -> BTreeMap<DepKind, BTreeSet<&'a Package>> {
+        let mut neighbors = BTreeMap::<_, BTreeSet<_>>::new();
+        if self.inverted {
+            for edge in self.graph.dependents(package) {
+                neighbors.entry(edge.kind).or_default().insert(&edge.from);
+            }
+        } else {
+            for edge in self.graph.dependencies(package) {
+                neighbors.entry(edge.kind).or_default().insert(&edge.to);
+            }
+        }
+        neighbors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{edge, package, resolved, EXAMPLE_SOURCE};
+
+    /// root -> a 1 -> b, root -> c -> a 1, root -(build)-> cc -> a 2,
+    /// root -(dev)-> b
+    fn graph() -> (DependencyGraph, [Package; 6]) {
+        let root = package("root", "1.0.0");
+        let a1 = package("a", "1.0.0");
+        let a2 = package("a", "2.0.0");
+        let b = package("b", "1.0.0");
+        let c = package("c", "1.0.0");
+        let cc = package("cc", "1.0.0");
+        let mut graph = DependencyGraph::new();
+        graph.add_root(resolved(&root, EXAMPLE_SOURCE, &[]));
+        for p in [&a1, &a2, &b, &c, &cc] {
+            graph.add_package(resolved(p, EXAMPLE_SOURCE, &[]));
+        }
+        graph.add_edge(edge(&root, &a1, DepKind::Normal, &[]));
+        graph.add_edge(edge(&a1, &b, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root, &c, DepKind::Normal, &[]));
+        graph.add_edge(edge(&c, &a1, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root, &cc, DepKind::Build, &[]));
+        graph.add_edge(edge(&cc, &a2, DepKind::Normal, &[]));
+        graph.add_edge(edge(&root, &b, DepKind::Development, &[]));
+        (graph, [root, a1, a2, b, c, cc])
+    }
+
+    fn render(tree: Tree) -> String {
+        let mut out = Vec::new();
+        tree.write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn tree() {
+        let (graph, _) = graph();
+        assert_eq!(
+            render(Tree::new(&graph)),
+            "\
+root v1.0.0
+├── a v1.0.0
+│   └── b v1.0.0
+└── c v1.0.0
+    └── a v1.0.0 (*)
+[build-dependencies]
+└── cc v1.0.0
+    └── a v2.0.0
+[dev-dependencies]
+└── b v1.0.0
+"
+        );
+        assert_eq!(
+            render(Tree::new(&graph).depth(1)),
+            "\
+root v1.0.0
+├── a v1.0.0
+└── c v1.0.0
+[build-dependencies]
+└── cc v1.0.0
+[dev-dependencies]
+└── b v1.0.0
+"
+        );
+    }
+
+    #[test]
+    fn inverted() {
+        let (graph, [_, _, _, b, ..]) = graph();
+        assert_eq!(
+            render(Tree::new(&graph).invert(&b)),
+            "\
+b v1.0.0
+└── a v1.0.0
+    ├── c v1.0.0
+    │   └── root v1.0.0
+    └── root v1.0.0
+[dev-dependencies]
+└── root v1.0.0
+"
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        let (graph, _) = graph();
+        assert_eq!(
+            render(Tree::new(&graph).duplicates()),
+            "\
+a v1.0.0
+├── c v1.0.0
+│   └── root v1.0.0
+└── root v1.0.0
+
+a v2.0.0
+└── cc v1.0.0
+    [build-dependencies]
+    └── root v1.0.0
+"
+        );
+    }
+}